@@ -0,0 +1,574 @@
+use std::{ collections::HashMap, ops::{ Not, Range } };
+use crate::{
+	ast::{ self, NodeOrToken, SyntaxKind, SyntaxNode, SyntaxToken },
+	output,
+	settings::{ Settings, NewlineStyle, DocCommentStyle },
+};
+
+use SyntaxKind as K;
+
+
+/// Replacement lines for comments changed by the comment pass.
+/// A run of line comments maps its first comment to all lines, the others to an empty list which removes them.
+pub type Replacements = HashMap<SyntaxToken, Vec<String>>;
+
+
+/// Languages and attributes rustdoc treats as Rust code.
+const RUST_ATTRIBUTES: [&str; 8] = [
+	"rust",
+	"ignore",
+	"no_run",
+	"should_panic",
+	"compile_fail",
+	"test_harness",
+	"standalone_crate",
+	"allow_fail",
+];
+
+
+fn line_marker(text: &str) -> &'static str {
+	match text {
+		_ if text.starts_with("////") => "////",
+		_ if text.starts_with("///") => "///",
+		_ if text.starts_with("//!") => "//!",
+		_ => "//",
+	}
+}
+
+
+pub fn is_line_doc(text: &str) -> bool {
+	matches!(line_marker(text), "///" | "//!")
+}
+
+
+fn is_block_doc(text: &str) -> bool {
+	(text.starts_with("/**") && text.starts_with("/***").not() && text != "/**/") || text.starts_with("/*!")
+}
+
+
+/// `Some(false)` for `// pretty-rusty: off`, `Some(true)` for `// pretty-rusty: on`.
+pub fn marker(text: &str) -> Option<bool> {
+	match text.strip_prefix("//")?.trim() {
+		"pretty-rusty: off" => Some(false),
+		"pretty-rusty: on" => Some(true),
+		_ => None,
+	}
+}
+
+
+/// Line comment of the same style on the line before, markers are never part of a run.
+fn follows(last: &SyntaxToken, token: &SyntaxToken) -> bool {
+	if marker(last.text()).is_some() || marker(token.text()).is_some() {
+		return false;
+	}
+	let Some(NodeOrToken::Token(whitespace)) = token.prev_sibling_or_token() else {
+		return false;
+	};
+	whitespace.kind() == K::WHITESPACE
+		&& whitespace.text().matches('\n').count() == 1
+		&& whitespace.prev_sibling_or_token() == Some(NodeOrToken::Token(last.clone()))
+		&& last.text().starts_with("//")
+		&& line_marker(last.text()) == line_marker(token.text())
+}
+
+
+/// Rewrite every comment below `node` based on the comment settings.
+/// This is the only place where pretty-rusty changes more than whitespace.
+pub fn rewrite(node: &SyntaxNode, settings: &Settings) -> Replacements {
+	let mut replacements = Replacements::new();
+	let active = settings.format_doc_comment_code
+		|| settings.comment_width.is_some()
+		|| settings.space_after_comment_marker
+		|| settings.trim_comment_trailing_whitespace
+		|| settings.doc_comment_style != DocCommentStyle::Preserve;
+	if active.not() {
+		return replacements;
+	}
+
+	let mut runs = Vec::<Vec<SyntaxToken>>::new();
+	for token in node.descendants_with_tokens().filter_map(NodeOrToken::into_token) {
+		if token.kind() != K::COMMENT {
+			continue;
+		}
+		let grouped = is_line_doc(token.text()) || (settings.comment_width.is_some() && token.text().starts_with("//"));
+		match runs.last_mut() {
+			// A comment after code on the same line never starts a run.
+			Some(run) if grouped && own_line(&run[0]) && run.last().is_some_and(|last| follows(last, &token)) => run.push(token),
+			_ => runs.push(vec![token]),
+		}
+	}
+
+	for run in runs {
+		let original = run.iter().map(|token| token.text().to_owned()).collect::<Vec<_>>();
+		let lines = match run[0].text() {
+			text if text.starts_with("//") => rewrite_lines(original.clone(), settings),
+			text => rewrite_block(text, alone(&run[0]), settings),
+		};
+		// Runs are kept together to be wrapped when they are emitted.
+		if lines == original && (settings.comment_width.is_none() || run.len() == 1) {
+			continue;
+		}
+		let mut lines = Some(lines);
+		for token in run {
+			replacements.insert(token, lines.take().unwrap_or_default());
+		}
+	}
+	replacements
+}
+
+
+/// Line comments, a run of doc comments or a single other comment.
+fn rewrite_lines(mut lines: Vec<String>, settings: &Settings) -> Vec<String> {
+	for line in &mut lines {
+		*line = line.trim_end_matches('\r').to_owned();
+	}
+	let doc = is_line_doc(&lines[0]);
+	if doc && settings.format_doc_comment_code {
+		for (range, formatted) in format_blocks(&lines, settings).into_iter().rev() {
+			lines.splice(range, formatted);
+		}
+	}
+	for line in &mut lines {
+		*line = normalize_line(line, settings);
+	}
+	if doc && settings.doc_comment_style == DocCommentStyle::Block {
+		return lines_to_block(&lines).unwrap_or(lines);
+	}
+	lines
+}
+
+
+fn normalize_line(line: &str, settings: &Settings) -> String {
+	let line = match settings.trim_comment_trailing_whitespace {
+		true => line.trim_end(),
+		false => line,
+	};
+	let marker = line_marker(line);
+	let rest = &line[marker.len()..];
+	let space = settings.space_after_comment_marker
+		&& marker != "////"
+		&& rest.starts_with(char::is_whitespace).not()
+		&& rest.is_empty().not();
+	match space {
+		true => format!("{} {}", marker, rest),
+		false => line.to_owned(),
+	}
+}
+
+
+/// Comment with nothing else on its lines.
+fn alone(token: &SyntaxToken) -> bool {
+	let followed = match token.next_sibling_or_token() {
+		Some(NodeOrToken::Token(whitespace)) => whitespace.kind() == K::WHITESPACE && whitespace.text().contains('\n'),
+		_ => false,
+	};
+	own_line(token) && followed
+}
+
+
+/// Block comments, doc comments are converted to line comments with `doc-comment-style = "line"`
+/// if they are `alone` on their lines, otherwise the code after them would become part of the comment.
+fn rewrite_block(text: &str, alone: bool, settings: &Settings) -> Vec<String> {
+	let text = text.replace("\r\n", "\n");
+	let mut lines = text.split('\n').map(String::from).collect::<Vec<_>>();
+	let doc = is_block_doc(&text);
+	if doc && settings.format_doc_comment_code {
+		for (range, formatted) in format_blocks(&lines, settings).into_iter().rev() {
+			lines.splice(range, formatted);
+		}
+	}
+	if doc && alone && settings.doc_comment_style == DocCommentStyle::Line {
+		if let Some(lines) = block_to_lines(&lines.join("\n")) {
+			return lines.iter().map(|line| normalize_line(line, settings)).collect();
+		}
+	}
+	if settings.trim_comment_trailing_whitespace {
+		for line in &mut lines {
+			*line = line.trim_end().to_owned();
+		}
+	}
+	vec![lines.join("\n")]
+}
+
+
+/// `///` lines as `/** */` block, `None` if a line contains the end of a block comment.
+fn lines_to_block(lines: &[String]) -> Option<Vec<String>> {
+	let open = match line_marker(&lines[0]) {
+		"//!" => "/*!",
+		_ => "/**",
+	};
+	let mut block = vec![open.to_owned()];
+	for line in lines {
+		let content = &line[3..];
+		let content = content.strip_prefix(' ').unwrap_or(content);
+		if content.contains("*/") {
+			return None;
+		}
+		block.push(match content.is_empty() {
+			true => " *".into(),
+			false => format!(" * {}", content),
+		});
+	}
+	block.push(" */".into());
+	Some(block)
+}
+
+
+/// `/** */` block as `///` lines, leading `*` of every line are removed.
+/// `None` if the block contains a nested comment.
+fn block_to_lines(text: &str) -> Option<Vec<String>> {
+	let marker = match text.starts_with("/*!") {
+		true => "//!",
+		false => "///",
+	};
+	let inner = text.get(3..text.len().checked_sub(2)?)?;
+	if text.ends_with("*/").not() || inner.contains("/*") || inner.contains("*/") {
+		return None;
+	}
+	let lines = inner.split('\n').collect::<Vec<_>>();
+	let rest = lines.iter().skip(1).filter(|line| line.trim().is_empty().not());
+	let starred = rest.clone().all(|line| line.trim_start().starts_with('*'));
+	let common = rest.map(|line| line.len() - line.trim_start().len()).min().unwrap_or_default();
+	let mut contents = lines.iter().enumerate()
+		.map(|(index, line)| match index {
+			0 => line.strip_prefix(' ').unwrap_or(line),
+			_ if starred => {
+				let line = line.trim_start();
+				let line = line.strip_prefix('*').unwrap_or(line);
+				line.strip_prefix(' ').unwrap_or(line)
+			}
+			_ => line.get(common..).unwrap_or_default(),
+		})
+		.map(str::trim_end)
+		.collect::<Vec<_>>();
+	while contents.first().is_some_and(|line| line.is_empty()) {
+		contents.remove(0);
+	}
+	while contents.last().is_some_and(|line| line.is_empty()) {
+		contents.pop();
+	}
+	let lines = contents.into_iter()
+		.map(|content| match content.is_empty() {
+			true => marker.to_owned(),
+			false => format!("{} {}", marker, content),
+		})
+		.collect::<Vec<_>>();
+	lines.is_empty().not().then_some(lines)
+}
+
+
+/// Comment starting a line.
+pub fn own_line(token: &SyntaxToken) -> bool {
+	match token.prev_sibling_or_token() {
+		None => true,
+		Some(NodeOrToken::Token(whitespace)) => whitespace.kind() == K::WHITESPACE && whitespace.text().contains('\n'),
+		Some(NodeOrToken::Node(_)) => false,
+	}
+}
+
+
+/// Paragraph of prose in a line comment, with the prefix of the first line and the following ones.
+struct Paragraph {
+	first: String,
+	rest: String,
+	lines: Vec<usize>,
+	words: Vec<String>,
+}
+
+
+/// Markdown list item or block quote, returns the prefix and the text.
+fn item(content: &str) -> Option<(&str, &str)> {
+	if let Some(text) = content.strip_prefix("> ") {
+		return Some(("> ", text));
+	}
+	let digits = content.chars().take_while(char::is_ascii_digit).count();
+	let length = match content.as_bytes() {
+		[b'-' | b'*' | b'+', b' ', ..] => 2,
+		_ if digits > 0 && (content[digits..].starts_with(". ") || content[digits..].starts_with(") ")) => digits + 2,
+		_ => return None,
+	};
+	Some(content.split_at(length))
+}
+
+
+/// Lines which are never joined with others, like headings, tables, fences, indented code
+/// and lines ending like commented out code.
+fn verbatim(content: &str) -> bool {
+	let trimmed = content.trim_start();
+	trimmed.is_empty()
+		|| trimmed.ends_with([';', '{', '}'])
+		|| content.starts_with("    ")
+		|| content.starts_with('\t')
+		|| ["#", "|", "```", "~~~", "<", "[^"].iter().any(|start| trimmed.starts_with(start))
+		|| (trimmed.starts_with('[') && trimmed.contains("]:"))
+}
+
+
+/// Text of a line continuing `paragraph`, `None` if the line starts something new.
+fn continuation<'a>(paragraph: &Paragraph, content: &'a str) -> Option<&'a str> {
+	let text = match paragraph.rest.as_str() {
+		"> " => content.strip_prefix("> ")?,
+		rest => {
+			if item(content).is_some() {
+				return None;
+			}
+			content.strip_prefix(rest)?
+		}
+	};
+	verbatim(text).not().then_some(text)
+}
+
+
+/// Wrap paragraphs of line comments containing a line longer than `width` columns, including the comment marker.
+/// Code blocks and other Markdown structures are kept as they are.
+pub fn reflow(lines: &[String], width: usize) -> Vec<String> {
+	let marker = line_marker(&lines[0]);
+	if marker == "////" || lines.iter().any(|line| line.starts_with("//").not() || line_marker(line) != marker) {
+		return lines.to_vec();
+	}
+	let mut result = Vec::new();
+	let mut paragraph = None::<Paragraph>;
+	let mut fenced = false;
+	for (index, line) in lines.iter().enumerate() {
+		let content = &line[marker.len()..];
+		let content = content.strip_prefix(' ').unwrap_or(content);
+		let trimmed = content.trim_start();
+		let fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+		let text = match fenced || fence {
+			false => paragraph.as_ref().and_then(|paragraph| continuation(paragraph, content)),
+			true => None,
+		};
+		if text.is_none() {
+			if let Some(paragraph) = paragraph.take() {
+				wrap(paragraph, lines, marker, width, &mut result);
+			}
+		}
+		if fence {
+			fenced = fenced.not();
+		}
+		let item = item(content);
+		if fenced || fence || (text.is_none() && item.is_none() && verbatim(content)) {
+			result.push(line.clone());
+			continue;
+		}
+		let (first, text) = match text {
+			Some(text) => ("", text),
+			None => item.unwrap_or(("", content)),
+		};
+		let current = paragraph.get_or_insert_with(|| Paragraph {
+			first: first.to_owned(),
+			rest: match first {
+				"> " => first.to_owned(),
+				_ => " ".repeat(first.chars().count()),
+			},
+			lines: Vec::new(),
+			words: Vec::new(),
+		});
+		current.lines.push(index);
+		current.words.extend(text.split_whitespace().map(String::from));
+		// Hard line breaks end the paragraph.
+		if content.ends_with("  ") || content.ends_with('\\') {
+			if let Some(paragraph) = paragraph.take() {
+				wrap(paragraph, lines, marker, width, &mut result);
+			}
+		}
+	}
+	if let Some(paragraph) = paragraph {
+		wrap(paragraph, lines, marker, width, &mut result);
+	}
+	result
+}
+
+
+/// Fill the lines greedily if any line of the paragraph is too long, otherwise keep them.
+fn wrap(paragraph: Paragraph, lines: &[String], marker: &str, width: usize, result: &mut Vec<String>) {
+	let original = paragraph.lines.iter().map(|&index| lines[index].clone());
+	if original.clone().all(|line| line.chars().count() <= width) {
+		return result.extend(original);
+	}
+	let available = width.saturating_sub(marker.len() + 1 + paragraph.first.chars().count());
+	let mut prefix = &paragraph.first;
+	let mut current = String::new();
+	for word in &paragraph.words {
+		if current.is_empty().not() && current.chars().count() + 1 + word.chars().count() > available {
+			result.push(format!("{} {}{}", marker, prefix, current));
+			prefix = &paragraph.rest;
+			current.clear();
+		}
+		if current.is_empty().not() {
+			current.push(' ');
+		}
+		current.push_str(word);
+	}
+	if let Some(&last) = paragraph.lines.last() {
+		if lines[last].ends_with("  ") {
+			current.push_str("  ");
+		}
+	}
+	result.push(format!("{} {}{}", marker, prefix, current));
+}
+
+
+/// Part of a line before the fence, a comment marker followed by whitespace and at most one `*`.
+fn fence(line: &str) -> Option<(&str, &str, &str)> {
+	let start = line.find("```").or_else(|| line.find("~~~"))?;
+	let (prefix, rest) = line.split_at(start);
+	let marker = ["///", "//!", "/**", "/*!"].iter()
+		.find_map(|marker| prefix.strip_prefix(marker))
+		.unwrap_or(prefix);
+	if marker.chars().any(|symbol| symbol.is_whitespace().not() && symbol != '*') || marker.matches('*').count() > 1 {
+		return None;
+	}
+	let symbol = rest.chars().next()?;
+	let length = rest.chars().take_while(|&other| other == symbol).count();
+	let (fence, info) = rest.split_at(length);
+	Some((prefix, fence, info.trim()))
+}
+
+
+fn is_rust(info: &str) -> bool {
+	info.split([',', ' ', '\t'])
+		.filter(|attribute| attribute.is_empty().not())
+		.all(|attribute| RUST_ATTRIBUTES.contains(&attribute) || attribute.starts_with("edition"))
+}
+
+
+/// Ranges of code lines inside Rust fences with their formatted replacement.
+fn format_blocks(lines: &[String], settings: &Settings) -> Vec<(Range<usize>, Vec<String>)> {
+	let mut blocks = Vec::new();
+	let mut index = 0;
+	while index < lines.len() {
+		index += 1;
+		let Some((prefix, open, info)) = fence(&lines[index - 1]) else {
+			continue;
+		};
+		let start = index;
+		let close = lines[start..].iter().position(|line| {
+			fence(line).is_some_and(|(_, close, info)| close.starts_with(open) && info.is_empty())
+		});
+		let Some(end) = close.map(|close| start + close) else {
+			break;
+		};
+		index = end + 1;
+		if is_rust(info).not() {
+			continue;
+		}
+		let code = lines[start..end].iter()
+			.map(|line| match line.strip_prefix(prefix) {
+				Some(code) => Some(code),
+				None => (line.trim_end() == prefix.trim_end()).then_some(""),
+			})
+			.collect::<Option<Vec<_>>>();
+		let Some(formatted) = code.and_then(|code| format_code(&code, settings)) else {
+			continue;
+		};
+		let formatted = formatted.into_iter()
+			.map(|line| match line.is_empty() {
+				true => prefix.trim_end().to_owned(),
+				false => format!("{}{}", prefix, line),
+			})
+			.collect::<Vec<_>>();
+		if formatted != lines[start..end] {
+			blocks.push((start..end, formatted));
+		}
+	}
+	blocks
+}
+
+
+/// Lines hidden from the documentation, starting with `# ` or only `#`.
+fn is_hidden(line: &str) -> bool {
+	let content = line.trim_start();
+	content == "#" || content.starts_with("# ")
+}
+
+
+fn non_whitespace(text: &str) -> impl Iterator<Item = char> + '_ {
+	text.chars().filter(|symbol| symbol.is_whitespace().not())
+}
+
+
+/// Format the visible lines of a code block, hidden lines are kept as they are at their position
+/// between the visible code, so hidden wrappers like `# fn main() {` never change the visible lines.
+fn format_code(lines: &[&str], settings: &Settings) -> Option<Vec<String>> {
+	let visible = lines.iter().copied().filter(|line| is_hidden(line).not()).collect::<Vec<_>>();
+	let mut formatted = format_snippet(&visible.join("\n"), settings)?;
+
+	// Hidden lines are inserted before the first formatted line which starts after the visible code before them.
+	let mut hidden = Vec::new();
+	let mut count = 0;
+	for line in lines {
+		match is_hidden(line) {
+			true => hidden.push((count, line.to_string())),
+			false => count += non_whitespace(line).count(),
+		}
+	}
+	let mut result = Vec::new();
+	let mut hidden = hidden.into_iter().peekable();
+	let mut count = 0;
+	for line in formatted.drain(..) {
+		while let Some((_, line)) = hidden.next_if(|&(position, _)| position <= count) {
+			result.push(line);
+		}
+		count += non_whitespace(&line).count();
+		result.push(line);
+	}
+	result.extend(hidden.map(|(_, line)| line));
+	Some(result)
+}
+
+
+/// Format code, wrapped in `fn main` if it does not parse as items.
+/// Fails if the code is empty or does not parse either way.
+fn format_snippet(code: &str, settings: &Settings) -> Option<Vec<String>> {
+	if code.trim().is_empty() {
+		return None;
+	}
+	let mut settings = settings.clone();
+	settings.final_newline = false;
+	settings.newline_style = NewlineStyle::Lf;
+	// Comments in the code stay as they are, otherwise the check for whitespace only changes fails.
+	settings.space_after_comment_marker = false;
+	settings.trim_comment_trailing_whitespace = false;
+	settings.doc_comment_style = DocCommentStyle::Preserve;
+	let indentation = output::indentation_text(output::level_columns(&settings), &settings);
+
+	let parse = ast::ast::SourceFile::parse(code);
+	let (formatted, wrapped) = match parse.errors().is_empty() {
+		true => (format(parse.syntax_node(), settings), false),
+		false => {
+			let parse = ast::ast::SourceFile::parse(&format!("fn main() {{\n{}\n}}", code));
+			if parse.errors().is_empty().not() {
+				return None;
+			}
+			(format(parse.syntax_node(), settings), true)
+		}
+	};
+	let mut formatted = formatted.split('\n').map(String::from).collect::<Vec<_>>();
+	if wrapped {
+		if formatted.len() < 2 {
+			return None;
+		}
+		formatted.pop();
+		formatted.remove(0);
+		for line in &mut formatted {
+			if let Some(content) = line.strip_prefix(&indentation) {
+				*line = content.to_owned();
+			}
+		}
+	}
+	while formatted.first().is_some_and(|line| line.trim().is_empty()) {
+		formatted.remove(0);
+	}
+	while formatted.last().is_some_and(|line| line.trim().is_empty()) {
+		formatted.pop();
+	}
+	non_whitespace(code).eq(formatted.iter().flat_map(|line| non_whitespace(line))).then_some(formatted)
+}
+
+
+fn format(node: SyntaxNode, settings: Settings) -> String {
+	let mut buffer = Vec::new();
+	crate::format_node(node, settings, &mut buffer);
+	String::from_utf8_lossy(&buffer).into_owned()
+}
@@ -0,0 +1,668 @@
+use std::{ collections::BTreeMap, fmt, ops::{ Not, Range }, path::{ Path, PathBuf } };
+use crate::{ Command, Error, CONFIG_NAME };
+use pretty_rusty::validation::Diagnostic;
+
+
+const CARGO_MANIFEST_NAME: &str = "Cargo.toml";
+const EDITOR_CONFIG_NAME: &str = ".editorconfig";
+
+
+#[derive(Debug, Clone)]
+pub enum Source {
+	User (PathBuf),
+	Project (PathBuf),
+	Cargo (PathBuf, &'static str),
+	EditorConfig (PathBuf),
+	CommandLine (String),
+}
+
+
+impl fmt::Display for Source {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::User(path) => write!(f, "user configuration '{}'", path.display()),
+			Self::Project(path) | Self::EditorConfig(path) => write!(f, "'{}'", path.display()),
+			Self::Cargo(path, table) => write!(f, "'{}' [{}]", path.display(), table),
+			Self::CommandLine(setting) => write!(f, "command line '--set {}'", setting),
+		}
+	}
+}
+
+
+impl Source {
+	/// Location used in diagnostics.
+	pub fn origin(&self) -> String {
+		match self {
+			Self::User(path) | Self::Project(path) | Self::EditorConfig(path) => path.display().to_string(),
+			Self::Cargo(path, table) => format!("{} [{}]", path.display(), table),
+			Self::CommandLine(setting) => format!("--set {}", setting),
+		}
+	}
+
+
+	/// File used in diagnostics, followed by the line and column.
+	fn location(&self) -> String {
+		match self {
+			Self::Cargo(path, _) => path.display().to_string(),
+			source => source.origin(),
+		}
+	}
+}
+
+
+/// Part of the layer data which was read from another file, like a property of an `.editorconfig`.
+/// Spans of the same length are mapped character by character, others point at the whole `span`.
+/// Without `span` diagnostics only name the file.
+#[derive(Debug, Clone)]
+pub struct Origin {
+	/// Span in the layer data.
+	pub data: Range<usize>,
+	pub path: PathBuf,
+	pub text: String,
+	/// Span in `text`.
+	pub span: Option<Range<usize>>,
+}
+
+
+impl Origin {
+	fn map(&self, span: &Range<usize>) -> Option<Range<usize>> {
+		let origin = self.span.as_ref()?;
+		match self.data.len() == origin.len() {
+			true => {
+				let start = origin.start + span.start - self.data.start;
+				Some(start..(start + span.len()).min(origin.end))
+			}
+			false => Some(origin.clone()),
+		}
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+	pub source: Source,
+	pub data: String,
+	pub origins: Vec<Origin>,
+}
+
+
+impl Layer {
+	/// Path used to match the `[[overrides]]` of this layer against the input file.
+	pub fn relative_path<'a>(&self, file: &'a Path) -> Option<&'a Path> {
+		match &self.source {
+			Source::User(_) => Some(file),
+			Source::Project(path) | Source::Cargo(path, _) => file.strip_prefix(path.parent()?).ok(),
+			Source::EditorConfig(_) | Source::CommandLine(_) => None,
+		}
+	}
+
+
+	/// Render a diagnostic at the position in the file the value was read from.
+	pub fn render(&self, diagnostic: &Diagnostic) -> String {
+		let origin = diagnostic.span.as_ref()
+			.and_then(|span| Some((span, self.origins.iter().find(|origin| origin.data.contains(&span.start))?)));
+		match origin {
+			Some((span, origin)) => Diagnostic { span: origin.map(span), ..diagnostic.clone() }
+				.render(&origin.path.display().to_string(), &origin.text),
+			None => diagnostic.render(&self.source.location(), &self.data),
+		}
+	}
+}
+
+
+/// Configuration layers ordered from lowest to highest precedence.
+pub fn layers(command: &Command) -> Result<Vec<Layer>, Error> {
+	let mut layers = Vec::new();
+
+	if command.use_configuration || command.validate_configuration || command.print_configuration.is_some() {
+		if let Some(file) = user_file() {
+			layers.push(Layer {
+				data: read(&file)?,
+				source: Source::User(file),
+				origins: Vec::new(),
+			});
+		}
+
+		if let Some(layer) = input_file(command)?.map(|file| editor_config_layer(&file)).transpose()?.flatten() {
+			layers.push(layer);
+		}
+
+		layers.extend(project_layers(&start_folder(command)?)?);
+	}
+
+	for setting in &command.set {
+		layers.push(Layer {
+			data: command_line_data(setting)?,
+			source: Source::CommandLine(setting.clone()),
+			origins: Vec::new(),
+		});
+	}
+
+	Ok(layers)
+}
+
+
+fn read(file: &Path) -> Result<String, Error> {
+	std::fs::read_to_string(file).map_err(Error::FailedToReadConfigurationFile)
+}
+
+
+/// Absolute path of the formatted file, used to search and match the configuration.
+pub fn input_file(command: &Command) -> Result<Option<PathBuf>, Error> {
+	let path = match (&command.print_configuration, &command.file_location, &command.path) {
+		(Some(path), _, _) | (_, Some(path), _) | (_, _, Some(path)) => path,
+		_ => return Ok(None),
+	};
+	let path = std::env::current_dir().map_err(Error::FailedToGetWorkingDirectory)?.join(path);
+	Ok((path.is_file() || (path.exists().not() && path.extension().is_some())).then_some(path))
+}
+
+
+fn start_folder(command: &Command) -> Result<PathBuf, Error> {
+	if let Some(file) = input_file(command)? {
+		return Ok(file.parent().ok_or(Error::FailedToGetProjectFolder)?.to_owned());
+	}
+	let folder = command.file_location.clone().or(command.path.clone()).unwrap_or_default();
+	Ok(std::env::current_dir().map_err(Error::FailedToGetWorkingDirectory)?.join(folder))
+}
+
+
+fn user_file() -> Option<PathBuf> {
+	let folder = match std::env::var_os("XDG_CONFIG_HOME") {
+		Some(folder) if folder.is_empty().not() => PathBuf::from(folder),
+		_ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+	};
+	let file = folder.join("pretty-rusty").join(CONFIG_NAME);
+	file.is_file().then_some(file)
+}
+
+
+/// Every configuration from the repository root down to `folder`, nearest last.
+/// In the same folder `pretty-rusty.toml` wins over the package metadata, which wins over the workspace metadata.
+fn project_layers(folder: &Path) -> Result<Vec<Layer>, Error> {
+	let mut layers = Vec::new();
+	for folder in folder.ancestors() {
+		let file = folder.join(CONFIG_NAME);
+		if file.is_file() {
+			layers.push(Layer {
+				data: read(&file)?,
+				source: Source::Project(file),
+				origins: Vec::new(),
+			});
+		}
+
+		let manifest = folder.join(CARGO_MANIFEST_NAME);
+		if manifest.is_file() {
+			let data = read(&manifest)?;
+			let table = toml::from_str::<toml::Table>(&data)
+				.map_err(|err| Error::MalformatedConfigurationFile(manifest.display().to_string(), err))?;
+			for (section, name) in [("package", "package.metadata.pretty-rusty"), ("workspace", "workspace.metadata.pretty-rusty")] {
+				let metadata = table.get(section)
+					.and_then(|section| section.get("metadata"))
+					.and_then(|metadata| metadata.get("pretty-rusty"));
+				if let Some(metadata) = metadata {
+					// Dotted keys and inline tables are serialized and point at the `pretty-rusty` key.
+					let (metadata, span) = match metadata_data(&data, name, metadata) {
+						Some(metadata) => (metadata, Some(0..data.len())),
+						None => (toml::to_string(metadata)?, metadata_span(&data, section)),
+					};
+					let origin = Origin { data: 0..metadata.len(), path: manifest.clone(), text: data.clone(), span };
+					layers.push(Layer {
+						data: metadata,
+						source: Source::Cargo(manifest.clone(), name),
+						origins: vec![origin],
+					});
+				}
+			}
+		}
+
+		if folder.join(".git").exists() {
+			break;
+		}
+	}
+	layers.reverse();
+	Ok(layers)
+}
+
+
+#[derive(serde::Deserialize)]
+struct Manifest {
+	package: Option<ManifestSection>,
+	workspace: Option<ManifestSection>,
+}
+
+
+#[derive(serde::Deserialize)]
+struct ManifestSection {
+	metadata: Option<ManifestMetadata>,
+}
+
+
+type ManifestMetadata = BTreeMap<toml::Spanned<String>, toml::Value>;
+
+
+/// Span of the `pretty-rusty` key in the metadata of `section`, toml has no spans for dotted tables.
+fn metadata_span(manifest: &str, section: &str) -> Option<Range<usize>> {
+	let manifest = toml::from_str::<Manifest>(manifest).ok()?;
+	let section = match section {
+		"package" => manifest.package,
+		_ => manifest.workspace,
+	};
+	section?.metadata?.into_keys()
+		.find(|key| key.get_ref() == "pretty-rusty")
+		.map(|key| key.span())
+}
+
+
+/// Replace every character of `line` except line endings with spaces of the same length.
+fn blank(line: &str) -> String {
+	line.chars()
+		.map(|symbol| match symbol {
+			'\n' | '\r' => symbol.to_string(),
+			_ => " ".repeat(symbol.len_utf8()),
+		})
+		.collect()
+}
+
+
+/// The manifest with everything outside the `[NAME]` tables blanked and `NAME.` removed from their headers.
+/// Offsets do not change, so diagnostics point at the manifest.
+/// `None` if the result is not `metadata`, like for inline tables or dotted keys.
+fn metadata_data(manifest: &str, name: &str, metadata: &toml::Value) -> Option<String> {
+	let mut data = String::with_capacity(manifest.len());
+	let mut inside = false;
+	for line in manifest.split_inclusive('\n') {
+		let header = line.trim_start();
+		if header.starts_with('[') {
+			let key = header.trim_start_matches('[').split(']').next().unwrap_or_default().trim();
+			inside = key == name || key.strip_prefix(name).is_some_and(|key| key.starts_with('.'));
+			if key == name {
+				data.push_str(&blank(line));
+				continue;
+			}
+			if inside {
+				let start = line.find(name)?;
+				data.push_str(&line[..start]);
+				data.push_str(&blank(&line[start..=start + name.len()]));
+				data.push_str(&line[start + name.len() + 1..]);
+				continue;
+			}
+		}
+		match inside {
+			true => data.push_str(line),
+			false => data.push_str(&blank(line)),
+		}
+	}
+	(toml::from_str::<toml::Value>(&data).ok()? == *metadata).then_some(data)
+}
+
+
+/// Values which are no valid TOML are used as strings, so `--set newline-style=lf` works without quotes.
+fn command_line_data(setting: &str) -> Result<String, Error> {
+	let (key, value) = setting
+		.split_once('=')
+		.ok_or_else(|| Error::MalformatedCommandLineSetting(setting.into()))?;
+	let (key, value) = (key.trim(), value.trim());
+	if key.is_empty() {
+		return Err(Error::MalformatedCommandLineSetting(setting.into()));
+	}
+	let data = format!("{} = {}", key, value);
+	if toml::from_str::<toml::Table>(&data).is_ok() {
+		return Ok(data);
+	}
+	Ok(format!("{} = {}", key, toml::Value::String(value.into())))
+}
+
+
+/// Settings from every `.editorconfig` between `file` and the first one with `root = true`.
+/// The files are shared with other tools, values which can not be used are ignored.
+fn editor_config_layer(file: &Path) -> Result<Option<Layer>, Error> {
+	let mut configurations = Vec::new();
+	for folder in file.ancestors().skip(1) {
+		let path = folder.join(EDITOR_CONFIG_NAME);
+		if path.is_file().not() {
+			continue;
+		}
+		let text = read(&path)?;
+		let (root, sections) = parse_editor_config(&text);
+		configurations.push((path, text, sections));
+		if root {
+			break;
+		}
+	}
+
+	let mut properties = BTreeMap::new();
+	for (index, (path, _, sections)) in configurations.iter().enumerate().rev() {
+		let Some(relative) = path.parent().and_then(|folder| file.strip_prefix(folder).ok()) else {
+			continue;
+		};
+		for (pattern, section_properties) in sections {
+			if editor_config_glob(pattern).is_some_and(|glob| glob.is_match(relative)) {
+				for property in section_properties {
+					properties.insert(property.key.clone(), (index, property));
+				}
+			}
+		}
+	}
+
+	// Every setting with the property it was translated from.
+	let mut settings = Vec::<(&str, toml::Value, &str)>::new();
+	let value = |key: &str| properties.get(key).map(|(_, property)| property.value.as_str());
+	let number = |key: &str| value(key).and_then(|value| value.parse::<u32>().ok()).filter(|&number| number > 0);
+	match value("indent_style") {
+		Some("tab") => settings.push(("indent-style", "tabs".into(), "indent_style")),
+		Some("space") => settings.push(("indent-style", "spaces".into(), "indent_style")),
+		_ => { }
+	}
+	let indent_size = match value("indent_size") {
+		Some("tab") => number("tab_width").map(|width| (width, "tab_width")),
+		_ => number("indent_size").map(|size| (size, "indent_size"))
+			.or(number("tab_width").map(|width| (width, "tab_width"))),
+	};
+	if let Some((size, property)) = indent_size {
+		settings.push(("indent-width", size.into(), property));
+	}
+	if let Some((width, property)) = number("tab_width").map(|width| (width, "tab_width")).or(indent_size) {
+		settings.push(("tab-width", width.into(), property));
+	}
+	match value("end_of_line") {
+		Some("lf") => settings.push(("newline-style", "lf".into(), "end_of_line")),
+		Some("crlf") => settings.push(("newline-style", "crlf".into(), "end_of_line")),
+		_ => { }
+	}
+	match value("insert_final_newline") {
+		Some("true") => settings.push(("final-newline", true.into(), "insert_final_newline")),
+		Some("false") => settings.push(("final-newline", false.into(), "insert_final_newline")),
+		_ => { }
+	}
+
+	let Some((path, _, _)) = configurations.first() else {
+		return Ok(None);
+	};
+	if settings.is_empty() {
+		return Ok(None);
+	}
+	let mut data = String::new();
+	let mut origins = Vec::new();
+	for (key, value, property) in settings {
+		data.push_str(&format!("{} = ", key));
+		let start = data.len();
+		data.push_str(&value.to_string());
+		if let Some((index, property)) = properties.get(property) {
+			let (path, text, _) = &configurations[*index];
+			origins.push(Origin { data: start..data.len(), path: path.clone(), text: text.clone(), span: Some(property.span.clone()) });
+		}
+		data.push('\n');
+	}
+	Ok(Some(Layer {
+		data,
+		source: Source::EditorConfig(path.clone()),
+		origins,
+	}))
+}
+
+
+/// Property of an `.editorconfig` with the span of its value, key and value are lowercase.
+#[derive(Debug)]
+struct Property {
+	key: String,
+	value: String,
+	span: Range<usize>,
+}
+
+
+type EditorConfigSection = (String, Vec<Property>);
+
+
+/// Returns if the file is marked as root and the properties for each section.
+fn parse_editor_config(data: &str) -> (bool, Vec<EditorConfigSection>) {
+	let mut root = false;
+	let mut sections = Vec::<EditorConfigSection>::new();
+	let mut offset = 0;
+	for line in data.split_inclusive('\n') {
+		let start = offset;
+		offset += line.len();
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+			continue;
+		}
+		if let Some(pattern) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+			sections.push((pattern.to_owned(), Vec::new()));
+			continue;
+		}
+		let Some((key, value)) = line.split_once('=') else {
+			continue;
+		};
+		let (key, value) = (key.trim(), value.trim());
+		let position = start + data[start..offset].find(line).unwrap_or_default() + line.len() - value.len();
+		let property = Property {
+			key: key.to_lowercase(),
+			value: value.to_lowercase(),
+			span: position..position + value.len(),
+		};
+		match sections.last_mut() {
+			Some((_, properties)) => properties.push(property),
+			None if property.key == "root" => root = property.value == "true",
+			None => { }
+		}
+	}
+	(root, sections)
+}
+
+
+/// Patterns without a slash match in every folder, all others relative to the `.editorconfig`.
+fn editor_config_glob(pattern: &str) -> Option<globset::GlobMatcher> {
+	let pattern = match pattern.strip_prefix('/') {
+		Some(pattern) => pattern.to_owned(),
+		None if pattern.contains('/') => pattern.to_owned(),
+		None => format!("**/{}", pattern),
+	};
+	let glob = globset::GlobBuilder::new(&pattern)
+		.literal_separator(true)
+		.build()
+		.ok()?;
+	Some(glob.compile_matcher())
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::Parser;
+	use pretty_rusty::Settings;
+
+
+	fn metadata(manifest: &str, section: &str) -> toml::Value {
+		let table = toml::from_str::<toml::Table>(manifest).expect("manifest is valid");
+		table[section]["metadata"]["pretty-rusty"].clone()
+	}
+
+
+	#[test]
+	fn package_metadata_keeps_offsets() {
+		let manifest = "[package]\nname = \"x\"\n\n[package.metadata.pretty-rusty]\nindent-width = 2\n\n[[package.metadata.pretty-rusty.overrides]]\nfiles = [\"tests/**\"]\n\n[dependencies]\nserde = \"1\"\n";
+		let name = "package.metadata.pretty-rusty";
+		let data = metadata_data(manifest, name, &metadata(manifest, "package")).expect("tables are translated");
+		assert_eq!(data.len(), manifest.len());
+		assert_eq!(data.find("indent-width"), manifest.find("indent-width"));
+		assert!(data.contains("[[                              overrides]]"));
+		assert!(data.contains("serde").not());
+	}
+
+
+	#[test]
+	fn workspace_metadata_keeps_offsets() {
+		let manifest = "[workspace]\nmembers = []\n\n[workspace.metadata.pretty-rusty]\ntab-width = 8\n\n[workspace.metadata.pretty-rusty.macros]\nvec = \"list\"\n";
+		let name = "workspace.metadata.pretty-rusty";
+		let data = metadata_data(manifest, name, &metadata(manifest, "workspace")).expect("tables are translated");
+		assert_eq!(data.find("tab-width"), manifest.find("tab-width"));
+		assert_eq!(data.find("vec"), manifest.find("vec"));
+		assert_eq!(metadata_span(manifest, "workspace"), manifest.find("pretty-rusty").map(|start| start..start + 12));
+	}
+
+
+	#[test]
+	fn dotted_metadata_points_at_key() {
+		let manifest = "[package]\nname = \"x\"\n\n[package.metadata]\npretty-rusty.indent-width = 2\n";
+		let name = "package.metadata.pretty-rusty";
+		assert_eq!(metadata_data(manifest, name, &metadata(manifest, "package")), None);
+		let start = manifest.find("pretty-rusty.").expect("key exists");
+		assert_eq!(metadata_span(manifest, "package"), Some(start..start + 12));
+	}
+
+
+	#[test]
+	fn inline_metadata_points_at_key() {
+		let manifest = "[package]\nname = \"x\"\nmetadata.pretty-rusty = { indent-width = 2 }\n";
+		let name = "package.metadata.pretty-rusty";
+		assert_eq!(metadata_data(manifest, name, &metadata(manifest, "package")), None);
+		let start = manifest.find("pretty-rusty").expect("key exists");
+		assert_eq!(metadata_span(manifest, "package"), Some(start..start + 12));
+		assert_eq!(metadata_span(manifest, "workspace"), None);
+	}
+
+
+	/// Empty folder in the temporary folder, with a `.git` to stop the search.
+	fn folder(name: &str) -> PathBuf {
+		let folder = std::env::temp_dir().join(format!("pretty-rusty-{}-{}", name, std::process::id()));
+		_ = std::fs::remove_dir_all(&folder);
+		std::fs::create_dir_all(folder.join(".git")).expect("folder is writable");
+		folder
+	}
+
+
+	#[test]
+	fn editor_config_sections() {
+		let (root, sections) = parse_editor_config("root = true\n\n# comment\n[*.rs]\nIndent_Style = Tab\n\n[src/**.rs]\n; comment\nindent_size = 2\n");
+		assert!(root);
+		let sections = sections.iter()
+			.map(|(pattern, properties)| (pattern.as_str(), properties.iter().map(|property| (property.key.as_str(), property.value.as_str())).collect::<Vec<_>>()))
+			.collect::<Vec<_>>();
+		assert_eq!(sections, vec![("*.rs", vec![("indent_style", "tab")]), ("src/**.rs", vec![("indent_size", "2")])]);
+		assert!(parse_editor_config("[*]\nroot = true\n").0.not());
+	}
+
+
+	#[test]
+	fn editor_config_value_spans() {
+		let data = "[*]\n  indent_size =  4 \n";
+		let (_, sections) = parse_editor_config(data);
+		assert_eq!(&data[sections[0].1[0].span.clone()], "4");
+	}
+
+
+	#[test]
+	fn editor_config_globs() {
+		let matches = |pattern: &str, path: &str| editor_config_glob(pattern).is_some_and(|glob| glob.is_match(path));
+		assert!(matches("*.rs", "main.rs"));
+		assert!(matches("*.rs", "src/main.rs"));
+		assert!(matches("/*.rs", "src/main.rs").not());
+		assert!(matches("src/*.rs", "src/main.rs"));
+		assert!(matches("src/*.rs", "src/nested/main.rs").not());
+		assert!(matches("*.{rs,toml}", "Cargo.toml"));
+	}
+
+
+	#[test]
+	fn editor_config_precedence() {
+		let folder = folder("editor-config-precedence");
+		std::fs::create_dir_all(folder.join("src")).expect("folder is writable");
+		std::fs::write(folder.join(".editorconfig"), "root = true\n[*]\nindent_style = space\nindent_size = 8\nend_of_line = crlf\n").expect("file is writable");
+		std::fs::write(folder.join("src/.editorconfig"), "[*.rs]\nindent_size = 2\n[*.rs]\nend_of_line = lf\n").expect("file is writable");
+		let layer = editor_config_layer(&folder.join("src/main.rs")).expect("files are readable").expect("settings are found");
+		let data = toml::from_str::<toml::Table>(&layer.data).expect("data is valid");
+		assert_eq!(data["indent-style"].as_str(), Some("spaces"));
+		assert_eq!(data["indent-width"].as_integer(), Some(2));
+		assert_eq!(data["newline-style"].as_str(), Some("lf"));
+		_ = std::fs::remove_dir_all(folder);
+	}
+
+
+	#[test]
+	fn editor_config_stops_at_root() {
+		let folder = folder("editor-config-root");
+		std::fs::create_dir_all(folder.join("src")).expect("folder is writable");
+		std::fs::write(folder.join(".editorconfig"), "[*]\nindent_style = space\n").expect("file is writable");
+		std::fs::write(folder.join("src/.editorconfig"), "root = true\n[*]\nindent_size = 2\n").expect("file is writable");
+		let layer = editor_config_layer(&folder.join("src/main.rs")).expect("files are readable").expect("settings are found");
+		let data = toml::from_str::<toml::Table>(&layer.data).expect("data is valid");
+		assert_eq!(data.get("indent-style"), None);
+		_ = std::fs::remove_dir_all(folder);
+	}
+
+
+	#[test]
+	fn unusable_editor_config_values_are_ignored() {
+		let folder = folder("editor-config-unusable");
+		std::fs::write(folder.join(".editorconfig"), "root = true\n[*]\nindent_style = space\nindent_size = -2\ntab_width = 0\nend_of_line = cr\n").expect("file is writable");
+		let layer = editor_config_layer(&folder.join("main.rs")).expect("files are readable").expect("settings are found");
+		assert_eq!(layer.data, "indent-style = \"spaces\"\n");
+		assert!(crate::validation::validate(&layer.data).is_empty());
+
+		std::fs::write(folder.join(".editorconfig"), "root = true\n[*]\nindent_style = space\nindent_size = tab\n").expect("file is writable");
+		let layer = editor_config_layer(&folder.join("main.rs")).expect("files are readable").expect("settings are found");
+		assert!(crate::validation::validate(&layer.data).is_empty());
+		_ = std::fs::remove_dir_all(folder);
+	}
+
+
+	#[test]
+	fn project_layers_from_root_to_folder() {
+		let folder = folder("project-layers");
+		std::fs::create_dir_all(folder.join("crate/src")).expect("folder is writable");
+		std::fs::write(folder.join(CONFIG_NAME), "tab-width = 2").expect("file is writable");
+		std::fs::write(folder.join("crate").join(CONFIG_NAME), "tab-width = 3").expect("file is writable");
+		std::fs::write(folder.join("crate/Cargo.toml"), "[package]\nname = \"x\"\n\n[package.metadata.pretty-rusty]\ntab-width = 4\n").expect("file is writable");
+
+		let layers = project_layers(&folder.join("crate/src")).expect("files are readable");
+		let sources = layers.iter().map(|layer| layer.source.origin()).collect::<Vec<_>>();
+		let manifest = folder.join("crate/Cargo.toml").display().to_string();
+		assert_eq!(sources, vec![
+			folder.join(CONFIG_NAME).display().to_string(),
+			format!("{} [package.metadata.pretty-rusty]", manifest),
+			folder.join("crate").join(CONFIG_NAME).display().to_string(),
+		]);
+		let (settings, _) = Settings::from_layers(&layers.iter().map(|layer| (layer.data.as_str(), None)).collect::<Vec<_>>())
+			.expect("layers are valid");
+		assert_eq!(settings.tab_width, 3);
+		_ = std::fs::remove_dir_all(folder);
+	}
+
+
+	#[test]
+	fn command_line_values() {
+		assert_eq!(command_line_data("tab-width=8").expect("setting is valid"), "tab-width = 8");
+		assert_eq!(command_line_data(" newline-style = lf ").expect("setting is valid"), "newline-style = \"lf\"");
+		assert_eq!(command_line_data("pad-parenthesis=true").expect("setting is valid"), "pad-parenthesis = true");
+		assert!(command_line_data("tab-width").is_err());
+		assert!(command_line_data("=8").is_err());
+	}
+
+
+	#[test]
+	fn command_line_layers_come_last() {
+		let folder = folder("command-line-layers");
+		std::fs::write(folder.join(CONFIG_NAME), "tab-width = 2").expect("file is writable");
+		let file = folder.join("main.rs");
+		let command = Command::parse_from(["pretty-rusty", &file.display().to_string(), "--use-configuration", "--set", "tab-width=8"]);
+		let layers = layers(&command).expect("layers are readable");
+		let sources = layers.iter()
+			.filter(|layer| matches!(layer.source, Source::User(_)).not())
+			.map(|layer| layer.source.origin())
+			.collect::<Vec<_>>();
+		assert_eq!(sources, vec![folder.join(CONFIG_NAME).display().to_string(), "--set tab-width=8".to_owned()]);
+		_ = std::fs::remove_dir_all(folder);
+	}
+
+
+	#[test]
+	fn overrides_match_relative_to_their_file() {
+		let layer = |source| Layer { source, data: String::new(), origins: Vec::new() };
+		let file = Path::new("/repo/crate/tests/a.rs");
+		let project = layer(Source::Project(PathBuf::from("/repo/crate/pretty-rusty.toml")));
+		assert_eq!(project.relative_path(file), Some(Path::new("tests/a.rs")));
+		let cargo = layer(Source::Cargo(PathBuf::from("/repo/Cargo.toml"), "workspace.metadata.pretty-rusty"));
+		assert_eq!(cargo.relative_path(file), Some(Path::new("crate/tests/a.rs")));
+		assert_eq!(layer(Source::CommandLine("tab-width=2".into())).relative_path(file), None);
+	}
+}
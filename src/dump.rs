@@ -0,0 +1,152 @@
+use std::ops::{ Not, Range };
+use crate::ast::{ NodeOrToken, SyntaxElement, SyntaxNode };
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+	pub first: usize,
+	pub last: usize,
+}
+
+
+impl std::str::FromStr for LineRange {
+	type Err = String;
+
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		let (first, last) = text.split_once(':').unwrap_or((text, text));
+		let parse = |text: &str| text.trim().parse::<usize>().map_err(|err| format!("invalid line '{}': {}", text, err));
+		let range = Self { first: parse(first)?, last: parse(last)? };
+		if range.first == 0 || range.first > range.last {
+			return Err(format!("invalid line range '{}', expected 'first:last' starting at 1", text));
+		}
+		Ok(range)
+	}
+}
+
+
+impl LineRange {
+	fn offsets(&self, source: &str) -> Range<usize> {
+		let mut start = source.len();
+		let mut end = source.len();
+		let mut offset = 0;
+		for (index, line) in source.split_inclusive('\n').enumerate() {
+			let number = index + 1;
+			if number == self.first {
+				start = offset;
+			}
+			offset += line.len();
+			if number == self.last {
+				end = offset;
+				break;
+			}
+		}
+		start..end
+	}
+}
+
+
+fn visible(element: &SyntaxElement, range: &Option<Range<usize>>) -> bool {
+	let Some(range) = range else {
+		return true;
+	};
+	let text_range = element.text_range();
+	let (start, end) = (usize::from(text_range.start()), usize::from(text_range.end()));
+	start < range.end && (end > range.start || (start == end && start == range.start))
+}
+
+
+pub fn compact(node: &SyntaxNode, source: &str, lines: Option<LineRange>) -> String {
+	let range = lines.map(|lines| lines.offsets(source));
+	let mut dump = String::new();
+	compact_element(&node.clone().into(), &range, 0, &mut dump);
+	dump
+}
+
+
+fn compact_element(element: &SyntaxElement, range: &Option<Range<usize>>, depth: usize, dump: &mut String) {
+	if visible(element, range).not() {
+		return;
+	}
+	let text_range = element.text_range();
+	dump.push_str(&format!("{0: <1$}{2:?} {3:?}", "", depth * 2, element.kind(), text_range));
+	match element {
+		NodeOrToken::Token(token) => dump.push_str(&format!(" {:?}\n", token.text())),
+		NodeOrToken::Node(node) => {
+			dump.push('\n');
+			for child in node.children_with_tokens() {
+				compact_element(&child, range, depth + 1, dump);
+			}
+		}
+	}
+}
+
+
+pub fn json(node: &SyntaxNode, source: &str, lines: Option<LineRange>) -> String {
+	let range = lines.map(|lines| lines.offsets(source));
+	let value = json_element(&node.clone().into(), &range).unwrap_or_default();
+	serde_json::to_string_pretty(&value).unwrap_or_default()
+}
+
+
+fn json_element(element: &SyntaxElement, range: &Option<Range<usize>>) -> Option<serde_json::Value> {
+	if visible(element, range).not() {
+		return None;
+	}
+	let text_range = element.text_range();
+	let mut value = serde_json::json!({
+		"kind": format!("{:?}", element.kind()),
+		"start": usize::from(text_range.start()),
+		"end": usize::from(text_range.end()),
+	});
+	match element {
+		NodeOrToken::Token(token) => value["text"] = token.text().into(),
+		NodeOrToken::Node(node) => value["children"] = node
+			.children_with_tokens()
+			.filter_map(|child| json_element(&child, range))
+			.collect(),
+	}
+	Some(value)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ast;
+
+
+	#[test]
+	fn line_ranges() {
+		assert_eq!("2:3".parse(), Ok(LineRange { first: 2, last: 3 }));
+		assert_eq!("4".parse(), Ok(LineRange { first: 4, last: 4 }));
+		assert!("0:1".parse::<LineRange>().is_err());
+		assert!("3:2".parse::<LineRange>().is_err());
+		assert!("a:b".parse::<LineRange>().is_err());
+		assert_eq!(LineRange { first: 2, last: 2 }.offsets("a\nbc\nd"), 2..5);
+	}
+
+
+	#[test]
+	fn compact_is_indented_by_depth() {
+		let source = "fn f() {}";
+		let dump = compact(&ast::SourceFile::parse(source).syntax_node(), source, None);
+		let lines = dump.lines().collect::<Vec<_>>();
+		assert_eq!(lines[0], "SOURCE_FILE 0..9");
+		assert_eq!(lines[1], "  FN 0..9");
+		assert_eq!(lines[2], "    FN_KW 0..2 \"fn\"");
+	}
+
+
+	#[test]
+	fn lines_restrict_the_dump() {
+		let source = "fn f() {}\nfn g() {}\n";
+		let root = ast::SourceFile::parse(source).syntax_node();
+		let dump = compact(&root, source, Some(LineRange { first: 2, last: 2 }));
+		assert!(dump.contains("\"g\""));
+		assert!(dump.contains("\"f\"").not());
+		let value = serde_json::from_str::<serde_json::Value>(&json(&root, source, Some(LineRange { first: 2, last: 2 }))).expect("dump is JSON");
+		assert_eq!(value["kind"], "SOURCE_FILE");
+		assert_eq!(value["children"][0]["start"], 10);
+	}
+}
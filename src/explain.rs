@@ -0,0 +1,129 @@
+use crate::{ ast::SyntaxKind, logic::Scope, output::Whitespace };
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct Explanation {
+	pub offset: usize,
+	pub left: SyntaxKind,
+	pub input: Whitespace,
+	pub right: SyntaxKind,
+	pub parent: SyntaxKind,
+	pub scope: Scope,
+	pub output: Whitespace,
+	pub rule: &'static str,
+}
+
+
+/// Line and display column, tabs advance to the next multiple of `tab_width`.
+fn position(source: &str, offset: usize, tab_width: usize) -> (usize, usize) {
+	let before = &source[..offset.min(source.len())];
+	let line = before.matches('\n').count() + 1;
+	let tab_width = tab_width.max(1);
+	let column = before.rsplit('\n').next().unwrap_or_default().chars().fold(0, |column, symbol| match symbol {
+		'\t' => column + tab_width - column % tab_width,
+		_ => column + 1,
+	});
+	(line, column + 1)
+}
+
+
+pub fn table(explanations: &[Explanation], source: &str, tab_width: usize) -> String {
+	let header = ["position", "left", "input", "right", "parent", "scope", "output", "rule"]
+		.map(String::from);
+	let rows = explanations.iter().map(|explanation| {
+		let (line, column) = position(source, explanation.offset, tab_width);
+		[
+			format!("{}:{}", line, column),
+			format!("{:?}", explanation.left),
+			explanation.input.to_string(),
+			format!("{:?}", explanation.right),
+			format!("{:?}", explanation.parent),
+			explanation.scope.to_string(),
+			explanation.output.to_string(),
+			explanation.rule.to_string(),
+		]
+	}).collect::<Vec<_>>();
+
+	let mut widths = header.clone().map(|cell| cell.len());
+	for row in &rows {
+		for (width, cell) in widths.iter_mut().zip(row) {
+			*width = (*width).max(cell.len());
+		}
+	}
+
+	let mut report = String::new();
+	for row in std::iter::once(&header).chain(&rows) {
+		let line = row.iter()
+			.zip(widths)
+			.map(|(cell, width)| format!("{0: <1$}", cell, width))
+			.collect::<Vec<_>>()
+			.join(" | ");
+		report.push_str(line.trim_end());
+		report.push('\n');
+	}
+	report
+}
+
+
+pub fn json(explanations: &[Explanation], source: &str, tab_width: usize) -> String {
+	let values = explanations.iter().map(|explanation| {
+		let (line, column) = position(source, explanation.offset, tab_width);
+		serde_json::json!({
+			"line": line,
+			"column": column,
+			"offset": explanation.offset,
+			"left": format!("{:?}", explanation.left),
+			"input": explanation.input.to_string(),
+			"right": format!("{:?}", explanation.right),
+			"parent": format!("{:?}", explanation.parent),
+			"scope": explanation.scope.to_string(),
+			"output": explanation.output.to_string(),
+			"rule": explanation.rule,
+		})
+	}).collect::<Vec<_>>();
+	serde_json::to_string_pretty(&values).unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::ops::Not;
+	use crate::{ ast, explain_node, Settings };
+
+
+	fn explanations(source: &str) -> Vec<Explanation> {
+		explain_node(ast::SourceFile::parse(source).syntax_node(), Settings::default())
+	}
+
+
+	#[test]
+	fn position_expands_tabs() {
+		assert_eq!(position("a\n\tb", 3, 4), (2, 5));
+		assert_eq!(position("a\n  \tb", 5, 4), (2, 5));
+		assert_eq!(position("ab", 1, 0), (1, 2));
+	}
+
+
+	#[test]
+	fn every_whitespace_has_a_rule() {
+		let source = "fn f(a: u8) {}";
+		let explanations = explanations(source);
+		assert!(explanations.is_empty().not());
+		assert!(explanations.iter().all(|explanation| explanation.rule.is_empty().not()));
+		let report = table(&explanations, source, 4);
+		assert!(report.starts_with("position | left"));
+		assert_eq!(report.lines().count(), explanations.len() + 1);
+	}
+
+
+	#[test]
+	fn json_has_positions() {
+		let source = "fn f() {}";
+		let explanations = explanations(source);
+		let values = serde_json::from_str::<Vec<serde_json::Value>>(&json(&explanations, source, 4)).expect("report is JSON");
+		assert_eq!(values.len(), explanations.len());
+		assert_eq!(values[0]["line"], 1);
+		assert_eq!(values[0]["offset"], explanations[0].offset);
+	}
+}
@@ -1,24 +1,50 @@
+mod comments;
+pub mod dump;
+pub mod explain;
 mod logic;
+mod macros;
 mod output;
 pub mod settings;
 mod state;
+pub mod validation;
 
 
 use output::Output;
-pub use settings::Settings;
-pub use output::Target;
+pub use settings::{ Settings, Preset, MacroShape, DocCommentStyle };
+pub use output::{ Target, Whitespace };
+pub use logic::Scope;
+pub use explain::Explanation;
 use state::State;
 
 pub use ra_ap_syntax as ast;
 
 
+fn new_state(node: &ast::SyntaxNode, settings: Settings) -> State {
+	let replacements = comments::rewrite(node, &settings);
+	let mut state = State::new(settings);
+	state.set_replacements(replacements);
+	state
+}
+
+
 pub fn format_node(
 	node: ast::SyntaxNode,
-	settings: Settings,
+	mut settings: Settings,
 	target: &mut impl Target,
 ) {
+	settings.newline_style = settings.newline_style.resolve(&node.text().to_string());
 	let mut output = Output::new(target);
-	let mut state = State::new(settings);
+	let mut state = new_state(&node, settings);
 	logic::format_node(&node, ast::SyntaxKind::SOURCE_FILE, &mut state, &mut output);
 	output.finish(&state);
 }
+
+
+pub fn explain_node(node: ast::SyntaxNode, settings: Settings) -> Vec<Explanation> {
+	let mut target = std::io::sink();
+	let mut output = Output::new(&mut target);
+	let mut state = new_state(&node, settings);
+	state.record_explanations();
+	logic::format_node(&node, ast::SyntaxKind::SOURCE_FILE, &mut state, &mut output);
+	state.take_explanations()
+}
@@ -1,6 +1,10 @@
+use std::ops::Not;
 use crate::{
-	ast::{ SyntaxKind, NodeOrToken, SyntaxNode, SyntaxToken },
-	output::{ Output, Target, Whitespace },
+	ast::{ self, AstNode, SyntaxKind, NodeOrToken, SyntaxNode, SyntaxToken, SyntaxElement },
+	comments,
+	explain::Explanation,
+	macros,
+	output::{ self, Output, Target, Whitespace },
 	state::State,
 };
 
@@ -17,54 +21,143 @@ pub enum Scope {
 }
 
 
+impl std::fmt::Display for Scope {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Default => write!(f, "default"),
+			Self::CompactList => write!(f, "compact-list"),
+			Self::PaddedList => write!(f, "padded-list"),
+			Self::MultilineList => write!(f, "multiline-list"),
+		}
+	}
+}
+
+
+/// Comments are wrapped after they are rewritten, so block doc comments converted to `///` are wrapped as well.
 fn format_token(token: &SyntaxToken, state: &mut State, output: &mut Output<impl Target>) {
-	output.text(token.text(), state);
+	let wrap = state.settings().comment_width
+		.filter(|_| token.kind() == K::COMMENT && comments::own_line(token));
+	let lines = match (state.replacement(token), wrap) {
+		(Some(lines), _) => lines.to_vec(),
+		(None, Some(_)) => vec![token.text().trim_end_matches('\r').to_owned()],
+		(None, None) => return text(token, state, output),
+	};
+	let lines = match wrap {
+		Some(width) => {
+			let indentation = state.indentation() * output::level_columns(state.settings());
+			comments::reflow(&lines, width.saturating_sub(indentation))
+		}
+		None => lines,
+	};
+	for (index, line) in lines.iter().enumerate() {
+		if index > 0 {
+			output.whitespace(W::LineBreak, state);
+		}
+		line_comment(line, state, output);
+	}
 }
 
 
+/// Line endings inside string literals are part of the value and kept as they are.
+pub fn text(token: &SyntaxToken, state: &mut State, output: &mut Output<impl Target>) {
+	match token.kind() {
+		K::STRING | K::BYTE_STRING | K::C_STRING => output.literal(token.text(), state),
+		K::COMMENT if token.text().starts_with("//") => line_comment(token.text(), state, output),
+		_ => output.text(token.text(), state),
+	}
+}
+
+
+/// Line comments end before `\n` and would keep the `\r` of a CRLF line ending, it is emitted by the linebreak.
+/// Two trailing spaces in doc comments are a Markdown hard break and kept.
+fn line_comment(text: &str, state: &mut State, output: &mut Output<impl Target>) {
+	let text = text.trim_end_matches('\r');
+	match comments::is_line_doc(text) && text.ends_with("  ") {
+		true => output.literal(text, state),
+		false => output.text(text, state),
+	}
+}
+
+
+/// `Some(false)` if the element is or starts with a `// pretty-rusty: off` comment, `Some(true)` for `on`.
+fn marker(element: &SyntaxElement) -> Option<bool> {
+	match element {
+		NodeOrToken::Token(token) => comments::marker(token.text()),
+		NodeOrToken::Node(node) => node.children_with_tokens()
+			.take_while(|child| matches!(child.kind(), K::COMMENT | K::WHITESPACE))
+			.filter_map(|child| comments::marker(&child.to_string()))
+			.last(),
+	}
+}
+
+
+/// Formatting is disabled after a `// pretty-rusty: off` comment until the next `on` comment
+/// or the closing delimiter of the list, the region is copied verbatim.
 fn format_children(node: &SyntaxNode, state: &mut State, parent: SyntaxKind, scope: Scope, output: &mut Output<impl Target>) {
 	let save = state.save();
+	let children = node.children_with_tokens().collect::<Vec<_>>();
+	let closing = children.iter()
+		.rposition(|child| child.kind() != K::WHITESPACE)
+		.filter(|&index| matches!(children[index].kind(), K::R_CURLY | K::R_PAREN | K::R_BRACK));
 	let mut ws = Whitespace::None;
-	let mut children = node.children_with_tokens();
-	let last = children.find(|node| node.kind() != SyntaxKind::WHITESPACE).unwrap();
-	match &last {
-		NodeOrToken::Token(token) => format_token(token, state, output),
-		NodeOrToken::Node(node) => format_node(node, node.kind(), state, output),
-	}
-	let mut last = last.kind();
-	for child in children {
-		match child {
-			NodeOrToken::Token(token) => {
-				if let SyntaxKind::WHITESPACE = token.kind() {
-					ws = Whitespace::from_text(token.text());
-					continue;
-				}
-				let ws = whitespace(
-					last,
-					std::mem::take(&mut ws),
-					token.kind(),
-					scope,
-					node.kind(),
-					state,
-				);
-				output.whitespace(ws, state);
-				format_token(&token, state, output);
-				last = token.kind();
+	let mut verbatim = None;
+	let mut last = None;
+	let mut off = false;
+	for (index, child) in children.iter().enumerate() {
+		if let NodeOrToken::Token(token) = child {
+			if token.kind() == K::WHITESPACE {
+				ws = Whitespace::from_text(token.text());
+				verbatim = Some(token.clone());
+				continue;
+			}
+		}
+		let marker = marker(child);
+		if off && (marker == Some(true) || Some(index) == closing) {
+			off = false;
+			state.set_verbatim_shift(None);
+		}
+		if off {
+			if let Some(whitespace) = verbatim.take() {
+				copy(&whitespace, state, output);
+			}
+			match child {
+				NodeOrToken::Token(token) => copy(token, state, output),
+				NodeOrToken::Node(child) => skip(child, state, output),
+			}
+			ws = Whitespace::None;
+			last = Some(child.kind());
+			continue;
+		}
+		if let NodeOrToken::Token(token) = child {
+			if state.replacement(token).is_some_and(<[String]>::is_empty) {
+				continue;
 			}
-			NodeOrToken::Node(child) => {
-				let ws = whitespace(
-					last,
-					std::mem::take(&mut ws),
-					child.kind(),
-					scope,
-					node.kind(),
-					state,
-				);
-				output.whitespace(ws, state);
-				format_node(&child, node.kind(), state, output);
-				last = child.kind();
+		}
+		match last {
+			Some(last) => separate(last, std::mem::take(&mut ws), child, scope, node.kind(), state, output),
+			None => ws = Whitespace::None,
+		}
+		if marker == Some(false) {
+			let first = match child {
+				NodeOrToken::Token(token) => Some(token.clone()),
+				NodeOrToken::Node(child) => child.first_token(),
+			};
+			if let Some(token) = first {
+				start_verbatim(&token, state);
 			}
 		}
+		match (child, last) {
+			(NodeOrToken::Node(child), _) if marker == Some(false) => skip(child, state, output),
+			(NodeOrToken::Token(token), _) => format_token(token, state, output),
+			(NodeOrToken::Node(child), None) => format_node(child, child.kind(), state, output),
+			(NodeOrToken::Node(child), Some(_)) => format_node(child, node.kind(), state, output),
+		}
+		off = marker == Some(false);
+		last = Some(child.kind());
+		verbatim = None;
+	}
+	if off {
+		state.set_verbatim_shift(None);
 	}
 	match parent {
 		K::LET_STMT | K::IF_EXPR => { },
@@ -73,18 +166,120 @@ fn format_children(node: &SyntaxNode, state: &mut State, parent: SyntaxKind, sco
 }
 
 
-fn skip(node: &SyntaxNode, state: &mut State, output: &mut Output<impl Target>) {
+fn separate(
+	left: SyntaxKind,
+	middle: Whitespace,
+	right: &SyntaxElement,
+	scope: Scope,
+	parent: SyntaxKind,
+	state: &mut State,
+	output: &mut Output<impl Target>,
+) {
+	let (ws, rule) = whitespace(left, middle, right.kind(), scope, parent, state);
+	state.explain(Explanation {
+		offset: (usize::from(right.text_range().start()) as isize + state.offset_shift()) as usize,
+		left,
+		input: middle,
+		right: right.kind(),
+		parent,
+		scope,
+		output: ws,
+		rule,
+	});
+	output.whitespace(ws, state);
+}
+
+
+/// Copy a node verbatim, with `reindent-verbatim` its lines move with the indentation of its first line.
+pub fn skip(node: &SyntaxNode, state: &mut State, output: &mut Output<impl Target>) {
+	if state.verbatim_shift().is_none() {
+		if let Some(token) = node.first_token().filter(|_| state.settings().reindent_verbatim) {
+			start_verbatim(&token, state);
+			skip(node, state, output);
+			state.set_verbatim_shift(None);
+			return;
+		}
+	}
 	for child in node.children_with_tokens() {
 		match child {
 			NodeOrToken::Node(node) => skip(&node, state, output),
-			NodeOrToken::Token(token) => output.text(token.text(), state),
+			NodeOrToken::Token(token) => copy(&token, state, output),
+		}
+	}
+}
+
+
+/// Copy a file without its trailing whitespace, which is added by `Output::finish`.
+fn skip_file(node: &SyntaxNode, state: &mut State, output: &mut Output<impl Target>) {
+	let mut children = node.children_with_tokens().collect::<Vec<_>>();
+	if children.last().is_some_and(|child| child.kind() == K::WHITESPACE) {
+		children.pop();
+	}
+	for child in children {
+		match child {
+			NodeOrToken::Node(node) => skip(&node, state, output),
+			NodeOrToken::Token(token) => copy(&token, state, output),
+		}
+	}
+}
+
+
+/// Start a verbatim region at `token`, shifted by the difference between the current indentation
+/// and the indentation of the line of `token` in the source.
+fn start_verbatim(token: &SyntaxToken, state: &mut State) {
+	if state.settings().reindent_verbatim.not() {
+		return;
+	}
+	let mut line = String::new();
+	let mut current = token.prev_token();
+	while let Some(previous) = current {
+		if let Some((_, tail)) = previous.text().rsplit_once('\n') {
+			line.insert_str(0, tail);
+			break;
 		}
+		line.insert_str(0, previous.text());
+		current = previous.prev_token();
 	}
+	let original = output::columns(&line, state.settings()) as isize;
+	let indentation = (state.indentation() * output::level_columns(state.settings())) as isize;
+	state.set_verbatim_shift(Some(indentation - original));
+}
+
+
+/// Copy a token of a verbatim region, string literals are never changed.
+fn copy(token: &SyntaxToken, state: &mut State, output: &mut Output<impl Target>) {
+	match state.verbatim_shift() {
+		Some(shift) if token.text().contains('\n') && matches!(token.kind(), K::STRING | K::BYTE_STRING | K::C_STRING).not() => {
+			let text = output::shift_lines(token.text(), shift, state.settings());
+			output.text(&text, state);
+		}
+		_ => text(token, state, output),
+	}
+}
+
+
+/// `#[rustfmt::skip]` or `#[pretty_rusty::skip]`, also as inner attribute.
+fn is_skip_attribute(node: &SyntaxNode) -> bool {
+	let Some(meta) = ast::ast::Attr::cast(node.clone()).and_then(|attribute| attribute.meta()) else {
+		return false;
+	};
+	let path = meta.path().map(|path| path.syntax().text().to_string().replace(char::is_whitespace, ""));
+	meta.token_tree().is_none()
+		&& meta.expr().is_none()
+		&& matches!(path.as_deref(), Some("rustfmt::skip" | "pretty_rusty::skip"))
 }
 
 
 pub fn format_node(node: &SyntaxNode, parent: SyntaxKind, state: &mut State, output: &mut Output<impl Target>) {
+	if node.children().any(|child| child.kind() == K::ATTR && is_skip_attribute(&child)) {
+		if node.kind() == K::SOURCE_FILE {
+			return skip_file(node, state, output);
+		}
+		return skip(node, state, output);
+	}
 	let scope = match node.kind() {
+		K::TOKEN_TREE if parent == K::MACRO_CALL => return macros::format_call(node, parent, state, output),
+		K::TOKEN_TREE if parent == K::MACRO_RULES => return macros::format_rules(node, state, output),
 		K::ERROR | K::TOKEN_TREE => return skip(node, state, output),
 
 		K::USE_TREE_LIST
@@ -131,6 +326,30 @@ pub fn format_node(node: &SyntaxNode, parent: SyntaxKind, state: &mut State, out
 }
 
 
+/// Indentation levels for a continuation line starting with `kind`.
+fn continuation(kind: SyntaxKind, state: &State) -> usize {
+	let settings = state.settings();
+	let specific = match kind {
+		K::DOT => settings.continuation_indent_chains,
+		K::PIPE | K::PIPE2 | K::AMP2
+			| K::PLUS | K::MINUS | K::STAR | K::SLASH | K::PERCENT
+			| K::AMP | K::CARET | K::SHL | K::SHR => settings.continuation_indent_binary,
+		K::WHERE_PRED => settings.continuation_indent_where,
+		_ => None,
+	};
+	specific.unwrap_or(settings.continuation_indent)
+}
+
+
+/// Keep up to `max` of the blank lines in `middle`.
+pub fn blank_lines(middle: Whitespace, max: usize) -> Whitespace {
+	match middle {
+		W::LineBreaks(amount) if max > 0 => W::LineBreaks(amount.min(max + 1)),
+		_ => W::LineBreak,
+	}
+}
+
+
 fn top_level(kind: SyntaxKind) -> bool {
 	matches!(kind, K::ASSOC_ITEM_LIST | K::ITEM_LIST |  K::SOURCE_FILE)
 }
@@ -143,63 +362,68 @@ fn whitespace(
 	scope: Scope,
 	parent: SyntaxKind,
 	state: &mut State,
-) -> Whitespace {
+) -> (Whitespace, &'static str) {
 	let ws = match (left, middle, right) {
 
 		// list open
 		(K::L_PAREN | K::L_BRACK | K::L_CURLY | K::L_ANGLE | K::PIPE, _, _) if scope == Scope::MultilineList => {
 			if matches!(right, K::R_PAREN | K::R_BRACK | K::R_CURLY | K::R_ANGLE | K::PIPE) {
 				match middle {
-					W::LineBreaks(_) => W::LineBreaks(2),
-					_ => W::LineBreak,
+					W::LineBreaks(_) => (W::LineBreaks(2), "list-open-empty-blank"),
+					_ => (W::LineBreak, "list-open-empty"),
 				}
+			} else if matches!(parent, K::STMT_LIST | K::ITEM_LIST | K::ASSOC_ITEM_LIST) && left == K::L_CURLY {
+				state.indent();
+				(blank_lines(middle, state.settings().blank_lines_after_block_open), "block-open")
 			} else {
 				state.indent();
-				W::LineBreak
+				(W::LineBreak, "list-open-multiline")
 			}
 		}
 		(K::L_PAREN | K::L_BRACK | K::L_CURLY | K::L_ANGLE | K::PIPE, _, _) if scope == Scope::PaddedList => {
 			state.enter_scope();
-			W::Space
+			(W::Space, "list-open-padded")
 		}
 		(K::L_PAREN | K::L_BRACK | K::L_CURLY | K::L_ANGLE | K::PIPE, _, _) if scope == Scope::CompactList => {
 			state.enter_scope();
-			W::None
+			(W::None, "list-open-compact")
 		}
 
 		// list close
 		(_, _, K::R_PAREN | K::R_BRACK | K::R_CURLY | K::R_ANGLE | K::PIPE) if scope == Scope::MultilineList => {
 			state.dedent();
-			W::LineBreak
+			(W::LineBreak, "list-close-multiline")
 		}
-		(_, _, K::R_PAREN | K::R_BRACK | K::R_CURLY | K::R_ANGLE | K::PIPE) if scope == Scope::PaddedList => W::Space,
-		(_, _, K::R_PAREN | K::R_BRACK | K::R_CURLY | K::R_ANGLE | K::PIPE) if scope == Scope::CompactList => W::None,
+		(_, _, K::R_PAREN | K::R_BRACK | K::R_CURLY | K::R_ANGLE | K::PIPE) if scope == Scope::PaddedList => (W::Space, "list-close-padded"),
+		(_, _, K::R_PAREN | K::R_BRACK | K::R_CURLY | K::R_ANGLE | K::PIPE) if scope == Scope::CompactList => (W::None, "list-close-compact"),
 
 		// list seperator
-		(_, _, K::COMMA) => W::None,
-		(K::COMMA, W::LineBreaks(_), _) if scope == Scope::MultilineList => W::LineBreaks(2),
-		(K::COMMA, _, _) if scope == Scope::MultilineList => W::LineBreak,
-		(K::COMMA, _, _) => W::Space,
+		(_, _, K::COMMA) => (W::None, "comma-before"),
+		(K::COMMA, W::LineBreaks(_), _) if scope == Scope::MultilineList => (W::LineBreaks(2), "comma-after-multiline-blank"),
+		(K::COMMA, _, _) if scope == Scope::MultilineList => (W::LineBreak, "comma-after-multiline"),
+		(K::COMMA, _, _) => (W::Space, "comma-after"),
 
 		//top items
+		(K::USE, W::LineBreaks(_), K::USE) => (W::LineBreaks(state.settings().blank_lines_between_use_groups + 1), "use-group-blank"),
 		(K::USE | K::CONST | K::TYPE_ALIAS, W::LineBreaks(_), K::USE | K::CONST | K::TYPE_ALIAS)
-			if left == right => W::LineBreaks(2),
-		(K::USE | K::CONST | K::TYPE_ALIAS, _, K::USE | K::CONST | K::TYPE_ALIAS) if left == right => W::LineBreak,
+			if left == right => (W::LineBreaks(2), "item-group-blank"),
+		(K::USE | K::CONST | K::TYPE_ALIAS, _, K::USE | K::CONST | K::TYPE_ALIAS) if left == right => (W::LineBreak, "item-group"),
 
-		(K::MODULE, _, K::MODULE) => W::LineBreak,
+		(K::MODULE, _, K::MODULE) => (W::LineBreak, "module-group"),
 		(_, _, K::USE | K::MODULE | K::FN | K::STRUCT | K::IMPL | K::ENUM | K::UNION
 			| K::MACRO_RULES | K::MACRO_CALL | K::TYPE_ALIAS | K::TRAIT)
-			=> W::LineBreaks(state.settings().blank_lines_around_items + 1),
+			=> (W::LineBreaks(state.settings().blank_lines_around_items + 1), "item-before"),
 		(K::USE | K::MODULE | K::FN | K::STRUCT | K::IMPL | K::ENUM | K::UNION
 			| K::MACRO_RULES | K::MACRO_CALL | K::TYPE_ALIAS | K::TRAIT, _, _)
-			=> W::LineBreaks(state.settings().blank_lines_around_items + 1),
+			=> (W::LineBreaks(state.settings().blank_lines_around_items + 1), "item-after"),
 
-		(_, _, K::CONST) if top_level(parent) => W::LineBreaks(state.settings().blank_lines_around_items + 1),
-		(K::CONST, _, _) if top_level(parent) => W::LineBreaks(state.settings().blank_lines_around_items + 1),
+		(_, _, K::CONST) if top_level(parent) => (W::LineBreaks(state.settings().blank_lines_around_items + 1), "const-before"),
+		(K::CONST, _, _) if top_level(parent) => (W::LineBreaks(state.settings().blank_lines_around_items + 1), "const-after"),
 
 		// statements
-		(K::EXPR_STMT | K::LET_STMT, W::LineBreaks(_), _) if scope == Scope::MultilineList => W::LineBreaks(2),
-		(K::EXPR_STMT | K::LET_STMT, _, _) if scope == Scope::MultilineList => W::LineBreak,
+		(K::EXPR_STMT | K::LET_STMT, W::LineBreaks(_), _) if scope == Scope::MultilineList
+			=> (blank_lines(middle, state.settings().max_blank_lines_in_blocks), "statement-after-blank"),
+		(K::EXPR_STMT | K::LET_STMT, _, _) if scope == Scope::MultilineList => (W::LineBreak, "statement-after"),
 
 		// chains
 		(
@@ -210,81 +434,86 @@ fn whitespace(
 				| K::AMP | K::CARET | K::SHL | K::SHR
 				| K::FOR_KW | K::MATCH_GUARD,
 		) => {
-			state.start_chain();
+			state.start_chain(continuation(right, state));
 			match middle {
-				W::LineBreaks(_) => W::LineBreaks(2),
-				_ => W::LineBreak
+				W::LineBreaks(_) => (W::LineBreaks(2), "chain-blank"),
+				_ => (W::LineBreak, "chain"),
 			}
 		}
 
 		(_, _, K::ASSOC_ITEM_LIST) if state.in_chain() => {
 			state.exit_chain();
-			W::LineBreak
+			(W::LineBreak, "chain-exit-items")
 		}
 
 		// tokens
-		(_, _, K::COLON2 | K::DOT2 | K::DOT2EQ) => W::None,
-		(K::COLON2 | K::DOT2 | K::DOT2EQ, _, _) => W::None,
+		(_, _, K::COLON2 | K::DOT2 | K::DOT2EQ) => (W::None, "path-before"),
+		(K::COLON2 | K::DOT2 | K::DOT2EQ, _, _) => (W::None, "path-after"),
 
-		(_, _, K::COLON) => W::None,
-		(K::COLON, _, _) => W::Space,
+		(_, _, K::COLON) => (W::None, "colon-before"),
+		(K::COLON, _, _) => (W::Space, "colon-after"),
 
-		(_, _, K::SEMICOLON | K::QUESTION) => W::None,
+		(_, _, K::SEMICOLON | K::QUESTION) => (W::None, "semicolon-before"),
 
-		(K::POUND, _, _) => W::None,
-		(K::BANG, _, _) if matches!(parent, K::ATTR | K::MACRO_CALL) => W::None,
-		(_, _, K::BANG) => W::None,
-		(K::AMP, _, _) if matches!(parent, K::SELF_PARAM | K::REF_TYPE | K::REF_EXPR | K::REF_PAT) => W::None,
+		(K::POUND, _, _) => (W::None, "pound-after"),
+		(K::BANG, _, _) if matches!(parent, K::ATTR | K::MACRO_CALL) => (W::None, "bang-after"),
+		(_, _, K::BANG) => (W::None, "bang-before"),
+		(K::AMP, _, _) if matches!(parent, K::SELF_PARAM | K::REF_TYPE | K::REF_EXPR | K::REF_PAT) => (W::None, "reference-after"),
 
-		(_, _, K::DOT) => W::None,
-		(K::DOT, _, _) => W::None,
+		(_, _, K::DOT) => (W::None, "dot-before"),
+		(K::DOT, _, _) => (W::None, "dot-after"),
 
-		(_, _, K::EQ) => W::Space,
-		(K::EQ, _, _) => W::Space,
+		(_, _, K::EQ) => (W::Space, "eq-before"),
+		(K::EQ, _, _) => (W::Space, "eq-after"),
 
-		(_, _, K::L_PAREN) => W::None,
+		(_, _, K::L_PAREN) => (W::None, "paren-before"),
 
 		// other
 		(K::ATTR, _, _)
 			if matches!(parent, K::FN | K::STRUCT | K::ENUM | K::UNION | K::VARIANT | K::RECORD_FIELD | K::MACRO_RULES)
-			=> W::LineBreak,
-		(_, _, K::META) => W::None,
-		(K::META, _, _) => W::None,
-		(K::PATH, _, _) if matches!(parent, K::META | K::TUPLE_STRUCT_PAT) => W::None,
+			=> (W::LineBreak, "attribute-after"),
+		(_, _, K::META) => (W::None, "meta-before"),
+		(K::META, _, _) => (W::None, "meta-after"),
+		(K::PATH, _, _) if matches!(parent, K::META | K::TUPLE_STRUCT_PAT) => (W::None, "path-in-pattern"),
 
-		(K::MATCH_ARM, W::LineBreaks(_), _) if scope == Scope::MultilineList => W::LineBreaks(2),
-		(K::MATCH_ARM, _, _) if scope == Scope::MultilineList => W::LineBreak,
+		(K::MATCH_ARM, W::LineBreaks(_), _) if scope == Scope::MultilineList => (W::LineBreaks(2), "match-arm-after-blank"),
+		(K::MATCH_ARM, _, _) if scope == Scope::MultilineList => (W::LineBreak, "match-arm-after"),
 
 		(_, _, K::PARAM_LIST | K::TUPLE_FIELD_LIST | K::GENERIC_PARAM_LIST | K::GENERIC_ARG_LIST)
-			if parent != K::CLOSURE_EXPR => W::None,
-		(K::STAR, _, K::CONST_KW) => W::None,
-		(K::STAR | K::PLUS | K::MINUS | K::BANG, _, _) if matches!(parent, K::PREFIX_EXPR | K::PTR_TYPE) => W::None,
-		(_, _, K::L_BRACK) if parent == K::INDEX_EXPR => W::None,
+			if parent != K::CLOSURE_EXPR => (W::None, "parameters-before"),
+		(K::STAR, _, K::CONST_KW) => (W::None, "pointer-const"),
+		(K::STAR | K::PLUS | K::MINUS | K::BANG, _, _) if matches!(parent, K::PREFIX_EXPR | K::PTR_TYPE) => (W::None, "prefix-after"),
+		(_, _, K::L_BRACK) if parent == K::INDEX_EXPR => (W::None, "index-before"),
 
-		(_, _, K::ARG_LIST) => W::None,
+		(_, _, K::ARG_LIST) => (W::None, "arguments-before"),
 
-		(_, _, K::WHERE_CLAUSE) => W::LineBreak,
-		(K::WHERE_CLAUSE, _, _) => W::LineBreak,
+		(_, _, K::WHERE_CLAUSE) => (W::LineBreak, "where-before"),
+		(K::WHERE_CLAUSE, _, _) => (W::LineBreak, "where-after"),
 		(_, _, K::WHERE_PRED) => {
-			state.start_chain();
-			W::LineBreak
+			state.start_chain(continuation(right, state));
+			(W::LineBreak, "where-predicate")
 		}
 
 		(_, _, K::LET_ELSE | K::BLOCK_EXPR) if state.in_chain() => {
 			state.exit_chain();
-			W::LineBreak
+			(W::LineBreak, "chain-exit-block")
 		}
 
-		(_, W::LineBreaks(_), _) if parent == K::STMT_LIST => W::LineBreaks(2),
-		(_, _, _) if parent == K::STMT_LIST => W::LineBreak,
-		(_, _, _) => W::Space,
+		(_, W::LineBreaks(_), _) if parent == K::STMT_LIST => (blank_lines(middle, state.settings().max_blank_lines_in_blocks), "block-blank"),
+		(_, _, _) if parent == K::STMT_LIST => (W::LineBreak, "block"),
+		(_, _, _) => (W::Space, "default"),
 	};
 
 	match (left, middle, right) {
-		(_, W::LineBreaks(_), K::COMMENT) => W::LineBreaks(2),
-		(_, _, K::COMMENT) => middle,
-		(K::COMMENT, W::LineBreaks(_), _) => W::LineBreaks(2),
-		(K::COMMENT, W::LineBreak, _) => W::LineBreak,
+		(K::L_CURLY, W::LineBreaks(_), K::COMMENT) if matches!(parent, K::STMT_LIST | K::ITEM_LIST | K::ASSOC_ITEM_LIST) => ws,
+		(_, W::LineBreaks(_), K::COMMENT) if parent == K::STMT_LIST && left != K::L_CURLY
+			=> (blank_lines(middle, state.settings().max_blank_lines_in_blocks), "comment-before-blank"),
+		(K::COMMENT, W::LineBreaks(_), _) if parent == K::STMT_LIST && right != K::R_CURLY
+			=> (blank_lines(middle, state.settings().max_blank_lines_in_blocks), "comment-after-blank"),
+		(_, W::LineBreaks(_), K::COMMENT) => (W::LineBreaks(2), "comment-before-blank"),
+		(_, _, K::COMMENT) => (middle, "comment-before"),
+		(K::COMMENT, W::LineBreaks(_), _) => (W::LineBreaks(2), "comment-after-blank"),
+		(K::COMMENT, W::LineBreak, _) => (W::LineBreak, "comment-after"),
 		(_, _, _) => ws
 	}
 }
@@ -0,0 +1,359 @@
+use std::ops::Not;
+use crate::{
+	ast::{ self, AstNode, SyntaxKind, NodeOrToken, SyntaxNode, SyntaxToken },
+	logic::{ self, format_node, skip },
+	output::{ self, Output, Target, Whitespace },
+	settings::{ Settings, MacroShape },
+	state::State,
+};
+
+use SyntaxKind as K;
+use Whitespace as W;
+
+
+/// Format the token tree of a macro call based on the shape configured for the macro name.
+/// With `auto` it is formatted like Rust code if it parses as an argument list, array or block.
+pub fn format_call(node: &SyntaxNode, parent: SyntaxKind, state: &mut State, output: &mut Output<impl Target>) {
+	let shape = name(node)
+		.and_then(|name| state.settings().macros.get(&name).copied())
+		.unwrap_or_default();
+	let kind = match shape {
+		MacroShape::Verbatim => return skip(node, state, output),
+		MacroShape::Block => K::STMT_LIST,
+		MacroShape::List => K::ARRAY_EXPR,
+		MacroShape::Auto => match node.first_token().map(|token| token.kind()) {
+			Some(K::L_PAREN) => K::ARG_LIST,
+			Some(K::L_BRACK) => K::ARRAY_EXPR,
+			Some(K::L_CURLY) => K::STMT_LIST,
+			_ => return skip(node, state, output),
+		},
+	};
+	match reparse(node, kind) {
+		Some(reparsed) if reparsed.text() == node.text() => format_reparsed(node, &reparsed, parent, state, output),
+		Some(reparsed) => format_delimited(node, &reparsed, parent, state, output),
+		None => skip(node, state, output),
+	}
+}
+
+
+/// Name of the called macro, without the path.
+fn name(node: &SyntaxNode) -> Option<String> {
+	let call = ast::ast::MacroCall::cast(node.parent()?)?;
+	Some(call.path()?.segment()?.name_ref()?.text().to_string())
+}
+
+
+fn format_reparsed(node: &SyntaxNode, reparsed: &SyntaxNode, parent: SyntaxKind, state: &mut State, output: &mut Output<impl Target>) {
+	let save = state.offset_shift();
+	let shift = usize::from(node.text_range().start()) as isize - usize::from(reparsed.text_range().start()) as isize;
+	state.set_offset_shift(save + shift);
+	format_node(reparsed, parent, state, output);
+	state.set_offset_shift(save);
+}
+
+
+/// Format a node reparsed with other delimiters and replace them with the delimiters of the macro call.
+fn format_delimited(node: &SyntaxNode, reparsed: &SyntaxNode, parent: SyntaxKind, state: &mut State, output: &mut Output<impl Target>) {
+	let (Some(open), Some(close)) = (node.first_token(), node.last_token()) else {
+		return skip(node, state, output);
+	};
+	let mut buffer = Vec::new();
+	format_reparsed(node, reparsed, parent, state, &mut Output::new(&mut buffer));
+	let text = String::from_utf8_lossy(&buffer);
+	let mut symbols = text.chars();
+	symbols.next();
+	symbols.next_back();
+	output.text(open.text(), state);
+	output.literal(symbols.as_str(), state);
+	output.text(close.text(), state);
+}
+
+
+/// Parse the text of a token tree inside a wrapper for a node of `kind`, with the delimiters `kind` requires.
+/// Fails if the parser reports any error or the found node does not cover the token tree exactly.
+fn reparse(node: &SyntaxNode, kind: SyntaxKind) -> Option<SyntaxNode> {
+	let text = node.text().to_string();
+	let inner = text.get(1..text.len().checked_sub(1)?)?;
+	let (text, source) = match kind {
+		K::ARG_LIST => (format!("({})", inner), format!("const _: () = f({});", inner)),
+		K::ARRAY_EXPR => (format!("[{}]", inner), format!("const _: () = [{}];", inner)),
+		_ => (format!("{{{}}}", inner), format!("fn f() {{{}}}", inner)),
+	};
+	let parse = ast::ast::SourceFile::parse(&source);
+	if parse.errors().is_empty().not() {
+		return None;
+	}
+	let reparsed = parse.syntax_node().descendants().find(|node| node.kind() == kind)?;
+	(reparsed.text() == text.as_str()).then_some(reparsed)
+}
+
+
+enum Rule {
+	Arm {
+		matcher: SyntaxNode,
+		transcriber: SyntaxNode,
+		semicolon: bool,
+	},
+	Comment (SyntaxToken),
+}
+
+
+/// Split the body of `macro_rules!` into arms and comments, with the amount of linebreaks before each.
+fn rules(node: &SyntaxNode) -> Option<Vec<(usize, Rule)>> {
+	let mut children = node.children_with_tokens().collect::<Vec<_>>();
+	if children.first()?.kind() != K::L_CURLY || children.last()?.kind() != K::R_CURLY {
+		return None;
+	}
+	children.pop();
+	let mut children = children.into_iter().skip(1).peekable();
+
+	let mut rules = Vec::new();
+	let mut linebreaks = 0;
+	while let Some(child) = children.next() {
+		match child {
+			NodeOrToken::Token(token) if token.kind() == K::WHITESPACE => {
+				linebreaks = token.text().matches('\n').count();
+				continue;
+			}
+			NodeOrToken::Token(token) if token.kind() == K::COMMENT => {
+				rules.push((linebreaks, Rule::Comment(token)));
+			}
+			NodeOrToken::Node(matcher) => {
+				let mut next = || children.find(|child| child.kind() != K::WHITESPACE);
+				let (Some(eq), Some(angle)) = (next(), next()) else {
+					return None;
+				};
+				if eq.kind() != K::EQ || angle.kind() != K::R_ANGLE || eq.text_range().end() != angle.text_range().start() {
+					return None;
+				}
+				let transcriber = next()?.into_node()?;
+				let semicolon = children.peek().is_some_and(|child| child.kind() == K::SEMICOLON);
+				if semicolon {
+					children.next();
+				}
+				rules.push((linebreaks, Rule::Arm { matcher, transcriber, semicolon }));
+			}
+			NodeOrToken::Token(_) => return None,
+		}
+		linebreaks = 0;
+	}
+	Some(rules)
+}
+
+
+/// Format the body of `macro_rules!` with one arm per line, unknown shapes are copied verbatim.
+pub fn format_rules(node: &SyntaxNode, state: &mut State, output: &mut Output<impl Target>) {
+	let Some(rules) = rules(node).filter(|rules| rules.is_empty().not()) else {
+		return skip(node, state, output);
+	};
+	let save = state.save();
+	output.text("{", state);
+	state.indent();
+	for (index, (linebreaks, rule)) in rules.into_iter().enumerate() {
+		match (linebreaks, &rule) {
+			(0, Rule::Comment(_)) if index > 0 => output.whitespace(W::Space, state),
+			(2.., _) if index > 0 => output.whitespace(W::LineBreaks(2), state),
+			_ => output.whitespace(W::LineBreak, state),
+		}
+		match rule {
+			Rule::Comment(token) => logic::text(&token, state, output),
+			Rule::Arm { matcher, transcriber, semicolon } => {
+				format_matcher(&matcher, state, output);
+				output.text(" => ", state);
+				format_transcriber(&transcriber, state, output);
+				if semicolon {
+					output.text(";", state);
+				}
+			}
+		}
+	}
+	state.dedent();
+	output.whitespace(W::LineBreak, state);
+	output.text("}", state);
+	state.restore(save);
+}
+
+
+fn tokens(node: &SyntaxNode) -> impl Iterator<Item = SyntaxToken> {
+	node.descendants_with_tokens().filter_map(NodeOrToken::into_token)
+}
+
+
+/// Closing delimiter of a repetition like `$( ... )`.
+fn closes_repetition(token: &SyntaxToken) -> bool {
+	let Some(tree) = token.parent() else {
+		return false;
+	};
+	let opening = tree.prev_sibling_or_token().is_some_and(|previous| previous.kind() == K::DOLLAR);
+	opening && tree.last_token().as_ref() == Some(token)
+}
+
+
+/// Punctuation which forms a single operator when written without space between.
+const OPERATORS: [&str; 24] = [
+	"=>", "->", "::", "..", "...", "..=", "==", "!=", "<=", ">=", "&&", "||", "<<", ">>",
+	"+=", "-=", "*=", "/=", "%=", "^=", "&=", "|=", "<<=", ">>=",
+];
+
+
+/// The tokens at `index - 1` and `index` are part of one operator in the source, like `=` and `>` in `=>`.
+fn operator_pair(tokens: &[SyntaxToken], index: usize) -> bool {
+	let adjacent = |first: usize, last: usize| {
+		last < tokens.len() && (first..last).all(|index| {
+			let (left, right) = (&tokens[index], &tokens[index + 1]);
+			left.kind().is_punct() && right.kind().is_punct() && left.text_range().end() == right.text_range().start()
+		})
+	};
+	let operator = |first: usize, last: usize| {
+		adjacent(first, last) && OPERATORS.contains(&tokens[first..=last].iter().map(SyntaxToken::text).collect::<String>().as_str())
+	};
+	operator(index - 1, index)
+		|| (index >= 2 && operator(index - 2, index))
+		|| operator(index - 1, index + 1)
+}
+
+
+/// Matchers are written on a single line with uniform spacing, like `($a:expr, $($b:ident),* $(,)?)`.
+/// Matchers with comments or linebreaks keep their lines.
+fn format_matcher(node: &SyntaxNode, state: &mut State, output: &mut Output<impl Target>) {
+	let tokens = tokens(node).filter(|token| token.kind() != K::WHITESPACE).collect::<Vec<_>>();
+	if tokens.iter().any(|token| token.kind() == K::COMMENT) || node.text().contains_char('\n') {
+		return format_lines(node, state, output);
+	}
+
+	let kinds = tokens.iter().map(SyntaxToken::kind).collect::<Vec<_>>();
+	let operator = |index: usize| matches!(kinds.get(index), Some(K::STAR | K::PLUS | K::QUESTION));
+	let mut joined = vec![false; tokens.len()];
+	for index in 1..tokens.len() {
+		let (left, right) = (&tokens[index - 1], &tokens[index]);
+		joined[index] |= operator_pair(&tokens, index);
+		joined[index] |= matches!(left.kind(), K::L_PAREN | K::L_BRACK | K::L_CURLY | K::DOLLAR | K::AT);
+		joined[index] |= matches!(right.kind(), K::R_PAREN | K::R_BRACK | K::R_CURLY | K::COMMA | K::SEMICOLON);
+		// `#[...]` and `name(...)`
+		joined[index] |= left.kind() == K::POUND && right.kind() == K::L_BRACK;
+		let operand = left.kind().is_punct().not() || matches!(left.kind(), K::R_PAREN | K::R_BRACK | K::R_ANGLE);
+		joined[index] |= operand && right.kind() == K::L_PAREN && left.text_range().end() == right.text_range().start();
+		// Unary `&$x`, `-$x` and `!$x`
+		let prefix = index < 2 || (kinds[index - 2].is_punct() && matches!(kinds[index - 2], K::R_PAREN | K::R_BRACK | K::R_CURLY).not());
+		joined[index] |= prefix && matches!(left.kind(), K::AMP | K::MINUS | K::BANG) && right.kind() == K::DOLLAR;
+		// `$name:fragment`
+		joined[index] |= right.kind() == K::COLON && index >= 2 && kinds[index - 2] == K::DOLLAR;
+		joined[index] |= left.kind() == K::COLON && index >= 3 && kinds[index - 3] == K::DOLLAR;
+		// `$( ... ),*`
+		if closes_repetition(left) {
+			let mut separator = index;
+			while separator + 1 < tokens.len() && operator_pair(&tokens, separator + 1) {
+				separator += 1;
+			}
+			if operator(index) {
+				joined[index] = true;
+			} else if operator(separator + 1) {
+				joined[index..=separator + 1].fill(true);
+			}
+		}
+	}
+
+	for (token, joined) in tokens.iter().zip(joined) {
+		if joined.not() && token != &tokens[0] {
+			output.text(" ", state);
+		}
+		logic::text(token, state, output);
+	}
+}
+
+
+/// Transcribers are formatted like a block if they parse, otherwise by their lines.
+fn format_transcriber(node: &SyntaxNode, state: &mut State, output: &mut Output<impl Target>) {
+	if node.first_token().is_some_and(|token| token.kind() == K::L_CURLY) {
+		if let Some(reparsed) = reparse(node, K::STMT_LIST) {
+			return format_reparsed(node, &reparsed, K::MACRO_RULES, state, output);
+		}
+	}
+	format_lines(node, state, output);
+}
+
+
+/// Single lines are padded like other delimiters, multiple lines are indented relative to the arm
+/// while keeping the indentation between them.
+fn format_lines(node: &SyntaxNode, state: &mut State, output: &mut Output<impl Target>) {
+	let tokens = tokens(node).collect::<Vec<_>>();
+	let [open, inner @ .., close] = tokens.as_slice() else {
+		return skip(node, state, output);
+	};
+	let matching = matches!(
+		(open.kind(), close.kind()),
+		(K::L_PAREN, K::R_PAREN) | (K::L_BRACK, K::R_BRACK) | (K::L_CURLY, K::R_CURLY)
+	);
+	// Error tolerant parsing produces token trees without their closing delimiter.
+	if matching.not() || close.parent().as_ref() != Some(node) {
+		return skip(node, state, output);
+	}
+	let pad = match open.kind() {
+		K::L_PAREN => state.settings().pad_parenthesis,
+		K::L_BRACK => state.settings().pad_square_brackets,
+		_ => state.settings().pad_curly_braces,
+	};
+
+	let lines = lines(inner, state.settings());
+	logic::text(open, state, output);
+	match lines.as_slice() {
+		[] => { }
+		[(_, _, line)] if inner.iter().all(|token| token.kind() != K::WHITESPACE || token.text().contains('\n').not()) => {
+			output.whitespace(if pad { W::Space } else { W::None }, state);
+			line.iter().for_each(|token| logic::text(token, state, output));
+			output.whitespace(if pad { W::Space } else { W::None }, state);
+		}
+		lines => {
+			let save = state.save();
+			state.indent();
+			let minimum = lines.iter()
+				.filter(|(linebreaks, _, _)| *linebreaks > 0)
+				.map(|(_, columns, _)| *columns)
+				.min()
+				.unwrap_or_default();
+			for (index, (linebreaks, columns, line)) in lines.iter().enumerate() {
+				match index {
+					0 => output.whitespace(W::LineBreak, state),
+					_ => output.whitespace(
+						logic::blank_lines(W::LineBreaks(*linebreaks), state.settings().max_blank_lines_in_blocks),
+						state,
+					),
+				}
+				if *linebreaks > 0 {
+					output.text(&output::indentation_text(columns - minimum, state.settings()), state);
+				}
+				line.iter().for_each(|token| logic::text(token, state, output));
+			}
+			state.restore(save);
+			output.whitespace(W::LineBreak, state);
+		}
+	}
+	logic::text(close, state, output);
+}
+
+
+/// Lines with the linebreaks before them, the columns of their indentation and their tokens.
+/// Linebreaks inside string literals or comments do not start a new line.
+fn lines(tokens: &[SyntaxToken], settings: &Settings) -> Vec<(usize, usize, Vec<SyntaxToken>)> {
+	let mut lines = vec![(0, 0, Vec::new())];
+	for token in tokens {
+		match lines.last_mut() {
+			Some((_, _, line)) if token.kind() != K::WHITESPACE => line.push(token.clone()),
+			Some((_, _, line)) if token.text().contains('\n').not() => line.push(token.clone()),
+			_ => {
+				let indentation = token.text().rsplit('\n').next().unwrap_or_default();
+				lines.push((token.text().matches('\n').count(), output::columns(indentation, settings), Vec::new()));
+			}
+		}
+	}
+	for (_, _, line) in &mut lines {
+		while line.last().is_some_and(|token| token.kind() == K::WHITESPACE) {
+			line.pop();
+		}
+		while line.first().is_some_and(|token| token.kind() == K::WHITESPACE) {
+			line.remove(0);
+		}
+	}
+	lines.retain(|(_, _, line)| line.is_empty().not());
+	lines
+}
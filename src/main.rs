@@ -1,9 +1,13 @@
-#![doc = include_str!("../readme.md")] #![forbid(unsafe_code, clippy::unwrap_used)]
+#![doc = include_str!("../readme.md")] #![forbid(unsafe_code)] #![deny(clippy::unwrap_used)]
 
 
-use std::{ path::PathBuf, io::{ Read, BufWriter }, ops::Not, fs::File };
+use std::{ collections::BTreeMap, path::PathBuf, io::{ Read, BufWriter }, fs::File };
 use clap::Parser;
-use pretty_rusty::{ Settings, format_node, ast };
+use pretty_rusty::{ Settings, format_node, explain_node, explain, dump, validation, ast };
+
+
+mod configuration;
+mod rustfmt;
 
 
 const CONFIG_NAME: &str = "pretty-rusty.toml";
@@ -17,14 +21,17 @@ pub enum Error {
 	#[error("Failed to get working directory")]
 	FailedToGetWorkingDirectory (std::io::Error),
 
-	#[error("No configuration file")]
-	NoConfigurationFile,
-
 	#[error("Failed to read configuration file")]
 	FailedToReadConfigurationFile (std::io::Error),
 
-	#[error("malformed configuration file: {0}")]
-	MalformatedConfigurationFile (#[from] toml::de::Error),
+	#[error("malformed configuration in {0}: {1}")]
+	MalformatedConfigurationFile (String, toml::de::Error),
+
+	#[error("configuration has {0} problem(s)")]
+	InvalidConfiguration (usize),
+
+	#[error("malformed setting '{0}', expected 'key=value'")]
+	MalformatedCommandLineSetting (String),
 
 	#[error("failed to serialize configuration: {0}")]
 	FailedToSerializeConfiguration (#[from] toml::ser::Error),
@@ -32,6 +39,9 @@ pub enum Error {
 	#[error("failed to save configuration file")]
 	FailedToSaveConfigurationFile (std::io::Error),
 
+	#[error("failed to read rustfmt configuration")]
+	FailedToReadRustfmtConfiguration (std::io::Error),
+
 	#[error("failed to read from stdin")]
 	FailedToReadStdIn (std::io::Error),
 
@@ -61,6 +71,20 @@ pub enum Error {
 }
 
 
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ExplainFormat {
+	Table,
+	Json,
+}
+
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum DumpFormat {
+	Compact,
+	Json,
+}
+
+
 #[derive(Debug, Clone, Parser)]
 pub struct Command {
 	/// Input path for source file, used as output path if nothing else is specified
@@ -75,10 +99,30 @@ pub struct Command {
 	#[arg(long, default_value_t = false)]
 	pub use_configuration: bool,
 
+	/// Overwrite a single setting, applied after all configuration files
+	#[arg(long = "set", value_name = "KEY=VALUE")]
+	pub set: Vec<String>,
+
+	/// Check all configuration layers for unknown keys and invalid values
+	#[arg(long = "validate-config", default_value_t = false)]
+	pub validate_configuration: bool,
+
 	/// Generate file with formatting settings based on the style
 	#[arg(long, default_value_t = false)]
 	pub save_configuration: bool,
 
+	/// Print the resolved settings for a file, annotated with the source of each value
+	#[arg(long = "print-config", value_name = "FILE", default_value = None)]
+	pub print_configuration: Option<PathBuf>,
+
+	/// Print the JSON schema for the configuration file
+	#[arg(long, default_value_t = false)]
+	pub print_config_schema: bool,
+
+	/// Translate a 'rustfmt.toml' into 'pretty-rusty.toml', written to the output path if specified
+	#[arg(long, value_name = "PATH", default_value = None)]
+	pub import_rustfmt: Option<PathBuf>,
+
 	/// Use standard input as source
 	#[arg(long, default_value_t = false)]
 	pub use_std_in: bool,
@@ -90,6 +134,18 @@ pub struct Command {
 	/// File location to search for configuration, defaults to input path if available
 	#[arg(long, default_value = None)]
 	pub file_location: Option<PathBuf>,
+
+	/// Print the rule that selected each whitespace instead of formatting
+	#[arg(long, value_enum, default_value = None)]
+	pub explain: Option<ExplainFormat>,
+
+	/// Print the syntax tree with kinds and text ranges instead of formatting
+	#[arg(long, value_enum, default_value = None)]
+	pub dump_ast: Option<DumpFormat>,
+
+	/// Restrict the syntax tree dump to the lines 'first:last'
+	#[arg(long, default_value = None, requires = "dump_ast")]
+	pub dump_lines: Option<dump::LineRange>,
 }
 
 
@@ -105,31 +161,80 @@ fn main() {
 
 
 fn format(command: &Command) -> Result<(), Error> {
-	let mut settings = Settings::default();
-
-	if command.use_configuration {
-		let path = match (&command.file_location, &command.path) {
-			(Some(path), _) => {
-				if path.extension().is_some() {
-					path.parent().ok_or(Error::FailedToGetProjectFolder)?.to_owned()
-				} else {
-					path.to_owned()
-				}
-			}
-			(_, Some(path)) => path.to_owned(),
-			_ => std::env::current_dir().map_err(Error::FailedToGetWorkingDirectory)?.to_owned(),
-		};
-		let mut path = path.as_path();
-		let file = loop {
-			let mut file = PathBuf::from(path);
-			file.push(CONFIG_NAME);
-			if file.is_file() {
-				break file;
+	if command.print_config_schema {
+		println!("{}", serde_json::to_string_pretty(&Settings::schema()).unwrap_or_default());
+		return Ok(());
+	}
+
+	if let Some(path) = &command.import_rustfmt {
+		let data = std::fs::read_to_string(path).map_err(Error::FailedToReadRustfmtConfiguration)?;
+		let (imported, unmapped) = rustfmt::import(&data)
+			.map_err(|err| Error::MalformatedConfigurationFile(path.display().to_string(), err))?;
+		let output = command.output.clone().unwrap_or(PathBuf::from(CONFIG_NAME));
+		std::fs::write(&output, toml::to_string_pretty(&imported)?)
+			.map_err(Error::FailedToSaveConfigurationFile)?;
+		for option in unmapped {
+			eprintln!("no equivalent for '{}'", option);
+		}
+		return Ok(());
+	}
+
+	let input_file = configuration::input_file(command)?;
+	let layers = configuration::layers(command)?;
+
+	if command.validate_configuration {
+		let mut problems = 0;
+		for layer in &layers {
+			for diagnostic in validation::validate(&layer.data) {
+				eprint!("{}", layer.render(&diagnostic));
+				problems += 1;
 			}
-			path = path.parent().ok_or(Error::NoConfigurationFile)?;
-		};
-		let data = std::fs::read_to_string(&file).map_err(Error::FailedToReadConfigurationFile)?;
-		settings.overwrite(&data)?;
+		}
+		if problems > 0 {
+			return Err(Error::InvalidConfiguration(problems));
+		}
+		eprintln!("configuration is valid");
+		return Ok(());
+	}
+
+	let mut preset = None;
+	for layer in &layers {
+		let diagnostics = validation::validate(&layer.data);
+		for diagnostic in &diagnostics {
+			eprint!("{}", layer.render(diagnostic));
+		}
+		let errors = diagnostics.iter()
+			.filter(|diagnostic| diagnostic.severity == validation::Severity::Error)
+			.count();
+		if errors > 0 {
+			return Err(Error::InvalidConfiguration(errors));
+		}
+		let layer_preset = Settings::preset(&layer.data)
+			.map_err(|err| Error::MalformatedConfigurationFile(layer.source.to_string(), err))?;
+		if let Some(layer_preset) = layer_preset {
+			preset = Some((layer_preset, layer.source.origin()));
+		}
+	}
+
+	let data = layers.iter()
+		.map(|layer| (layer.data.as_str(), input_file.as_deref().and_then(|file| layer.relative_path(file))))
+		.collect::<Vec<_>>();
+	let (settings, keys) = Settings::from_layers(&data)
+		.map_err(|err| Error::MalformatedConfigurationFile("configuration".into(), err))?;
+	let mut sources = BTreeMap::new();
+	for (layer, keys) in layers.iter().zip(keys) {
+		for key in keys {
+			sources.insert(key, layer.source.origin());
+		}
+	}
+
+	if command.print_configuration.is_some() {
+		if let Some((preset, source)) = &preset {
+			println!("preset = {} # {}", toml::Value::try_from(preset)?, source);
+		}
+		let base = if preset.is_some() { "preset" } else { "default" };
+		print!("{}", annotated_configuration(&settings, &sources, base)?);
+		return Ok(());
 	}
 
 	if command.save_configuration {
@@ -153,8 +258,23 @@ fn format(command: &Command) -> Result<(), Error> {
 		(None, false) => return Err(Error::NoInputFileOrStdInSpecified),
 	};
 	let root = ast::ast::SourceFile::parse(&input_data).syntax_node();
-	if command.use_std_out.not() {
-		println!("{:#?}", root);
+
+	if let Some(format) = command.explain {
+		let tab_width = settings.tab_width;
+		let explanations = explain_node(root, settings);
+		match format {
+			ExplainFormat::Table => print!("{}", explain::table(&explanations, &input_data, tab_width)),
+			ExplainFormat::Json => println!("{}", explain::json(&explanations, &input_data, tab_width)),
+		}
+		return Ok(());
+	}
+
+	if let Some(format) = command.dump_ast {
+		match format {
+			DumpFormat::Compact => print!("{}", dump::compact(&root, &input_data, command.dump_lines)),
+			DumpFormat::Json => println!("{}", dump::json(&root, &input_data, command.dump_lines)),
+		}
+		return Ok(());
 	}
 
 	match (&command.output, command.use_std_out) {
@@ -183,3 +303,61 @@ fn format(command: &Command) -> Result<(), Error> {
 
 	Ok(())
 }
+
+
+/// Settings as TOML, every value followed by a comment with the layer which set it.
+/// Unset options are commented out, entries of tables are annotated one by one.
+fn annotated_configuration(settings: &Settings, sources: &BTreeMap<String, String>, base: &str) -> Result<String, Error> {
+	let table = toml::Table::try_from(settings)?;
+	// TOML has no null, the keys of unset options are only known to JSON.
+	let keys = match serde_json::to_value(settings) {
+		Ok(serde_json::Value::Object(object)) => object.keys().cloned().collect(),
+		_ => table.keys().cloned().collect::<Vec<_>>(),
+	};
+	let mut values = String::new();
+	let mut tables = String::new();
+	for key in keys {
+		let source = sources.get(&key).map_or(base, String::as_str);
+		match table.get(&key) {
+			None => values.push_str(&format!("# {} = <unset> # {}\n", key, source)),
+			Some(toml::Value::Table(entries)) => {
+				tables.push_str(&format!("\n[{}]\n", key));
+				for (name, value) in entries {
+					let source = sources.get(&format!("{}.{}", key, name)).map_or(source, String::as_str);
+					let entry = toml::to_string(&toml::Table::from_iter([(name.clone(), value.clone())]))?;
+					tables.push_str(&format!("{} # {}\n", entry.trim_end(), source));
+				}
+			}
+			Some(value) => values.push_str(&format!("{} = {} # {}\n", key, value, source)),
+		}
+	}
+	Ok(values + &tables)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+
+	#[test]
+	fn annotated_configuration_lists_every_setting() {
+		let layers = [
+			("continuation-indent-chains = 2\n[macros]\nvec = \"list\"\njson = \"verbatim\"\n", None),
+			("[macros]\nvec = \"block\"\n", None),
+		];
+		let (settings, keys) = Settings::from_layers(&layers).expect("layers are valid");
+		let mut sources = BTreeMap::new();
+		for (source, keys) in ["first", "second"].into_iter().zip(keys) {
+			for key in keys {
+				sources.insert(key, source.to_owned());
+			}
+		}
+		let text = annotated_configuration(&settings, &sources, "default").expect("settings are serializable");
+		assert!(text.contains("\n# comment-width = <unset> # default\n"));
+		assert!(text.contains("\ncontinuation-indent-chains = 2 # first\n"));
+		assert!(text.contains("\n# continuation-indent-where = <unset> # default\n"));
+		assert!(text.ends_with("\n[macros]\njson = \"verbatim\" # first\nvec = \"block\" # second\n"));
+		assert!(toml::from_str::<toml::Table>(&text).is_ok());
+	}
+}
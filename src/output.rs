@@ -1,9 +1,11 @@
+use std::ops::Not;
 use crate::state::State;
-use super::settings::Settings;
+use super::settings::{ Settings, IndentStyle };
 
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Whitespace {
+	#[default]
 	None,
 	Space,
 	// Spaces (usize),
@@ -12,18 +14,11 @@ pub enum Whitespace {
 }
 
 
-impl Default for Whitespace {
-	fn default() -> Self {
-		Self::None
-	}
-}
-
-
 impl Whitespace {
 	pub fn from_text(text: &str) -> Self {
 		let linebreaks = text.chars().filter(| &c | c == '\n').count();
 		match linebreaks {
-			0 if text.len() == 0 => Self::Space,
+			0 if text.is_empty() => Self::Space,
 			0 => Self::Space,
 			1 => Self::LineBreak,
 			l => Self::LineBreaks(l),
@@ -32,6 +27,71 @@ impl Whitespace {
 }
 
 
+impl std::fmt::Display for Whitespace {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::None => write!(f, "none"),
+			Self::Space => write!(f, "space"),
+			Self::LineBreak => write!(f, "line-break"),
+			Self::LineBreaks(amount) => write!(f, "line-breaks({})", amount),
+		}
+	}
+}
+
+
+/// Width of the leading whitespace of `text` in columns.
+pub fn columns(text: &str, settings: &Settings) -> usize {
+	let tab_width = settings.tab_width.max(1);
+	text.chars()
+		.take_while(|symbol| matches!(symbol, ' ' | '\t'))
+		.fold(0, |column, symbol| match symbol {
+			'\t' => column + tab_width - column % tab_width,
+			_ => column + 1,
+		})
+}
+
+
+/// Columns of a single indentation level.
+pub fn level_columns(settings: &Settings) -> usize {
+	match settings.indent_style {
+		IndentStyle::Tabs => settings.tab_width.max(1),
+		IndentStyle::Spaces => settings.indent_width,
+	}
+}
+
+
+/// Whitespace spanning `columns`, using tabs where possible with `indent-style = "tabs"`.
+pub fn indentation_text(columns: usize, settings: &Settings) -> String {
+	match settings.indent_style {
+		IndentStyle::Tabs => {
+			let tab_width = settings.tab_width.max(1);
+			format!("{0:\t<1$}{0: <2$}", "", columns / tab_width, columns % tab_width)
+		}
+		IndentStyle::Spaces => format!("{0: <1$}", "", columns),
+	}
+}
+
+
+/// Move the lines after the first by `shift` columns.
+/// Blank lines are kept, the last line is always moved as it is the indentation of the text after it.
+pub fn shift_lines(text: &str, shift: isize, settings: &Settings) -> String {
+	let lines = text.split('\n').collect::<Vec<_>>();
+	let mut result = lines[0].to_owned();
+	for (index, line) in lines.iter().enumerate().skip(1) {
+		result.push('\n');
+		let content = line.trim_start_matches([' ', '\t']);
+		if content.trim().is_empty() && index + 1 < lines.len() {
+			result.push_str(line);
+			continue;
+		}
+		let columns = (columns(line, settings) as isize + shift).max(0) as usize;
+		result.push_str(&indentation_text(columns, settings));
+		result.push_str(content);
+	}
+	result
+}
+
+
 pub trait Target {
 	fn emit(&mut self, data: &str, settings: &Settings);
 }
@@ -46,6 +106,8 @@ impl <T: std::io::Write> Target for T {
 
 pub struct Output <'a, T: Target> {
 	target: &'a mut T,
+	/// Spaces and tabs at the end of the emitted text, held back with `trim-trailing-whitespace`.
+	pending: String,
 }
 
 
@@ -53,15 +115,46 @@ impl <'a, T: Target> Output<'a, T> {
 	pub fn new(target: &'a mut T) -> Self {
 		Self {
 			target,
+			pending: String::new(),
+		}
+	}
+
+
+	/// With `trim-trailing-whitespace` spaces and tabs before line endings are dropped,
+	/// at the end they are kept until something other than a line ending follows.
+	fn emit(&mut self, data: &str, settings: &Settings) {
+		if settings.trim_trailing_whitespace.not() {
+			return self.target.emit(data, settings);
+		}
+		let data = std::mem::take(&mut self.pending) + data;
+		let mut result = String::with_capacity(data.len());
+		let mut lines = data.split('\n').peekable();
+		while let Some(line) = lines.next() {
+			if lines.peek().is_none() {
+				let content = line.trim_end_matches([' ', '\t']);
+				result.push_str(content);
+				self.pending = line[content.len()..].to_owned();
+				break;
+			}
+			let (line, carriage_return) = match line.strip_suffix('\r') {
+				Some(line) => (line, "\r"),
+				None => (line, ""),
+			};
+			result.push_str(line.trim_end_matches([' ', '\t']));
+			result.push_str(carriage_return);
+			result.push('\n');
+		}
+		if result.is_empty().not() {
+			self.target.emit(&result, settings);
 		}
 	}
 
 
 	fn emit_indentation(&mut self, state: &State, settings: &Settings) {
-		match settings.indentation {
-			0 => self.target.emit(&format!("{0:\t<1$}", "", state.indentation()), settings),
-			amount => self.target.emit(
-				&format!("{0: <1$}", "", state.indentation() * amount),
+		match settings.indent_style {
+			IndentStyle::Tabs => self.emit(&format!("{0:\t<1$}", "", state.indentation()), settings),
+			IndentStyle::Spaces => self.emit(
+				&format!("{0: <1$}", "", state.indentation() * settings.indent_width),
 				settings,
 			),
 		}
@@ -71,27 +164,41 @@ impl <'a, T: Target> Output<'a, T> {
 	pub fn whitespace(&mut self, whitespace: Whitespace, state: &State) {
 		match whitespace {
 			Whitespace::None => { }
-			Whitespace::Space => self.target.emit(" ", &state.settings()),
+			Whitespace::Space => self.emit(" ", state.settings()),
 			// Whitespace::Spaces(amount) => {
-			// 	self.target.emit(&format!("{0: <1$}", "", amount), &state.settings());
+			// 	self.target.emit(&format!("{0: <1$}", "", amount), state.settings());
 			// }
 			Whitespace::LineBreak => {
-				self.target.emit("\n", &state.settings());
-				self.emit_indentation(state, &state.settings())
+				self.emit(state.settings().newline_style.as_str(), state.settings());
+				self.emit_indentation(state, state.settings())
 			}
 			Whitespace::LineBreaks(amount) => {
-				self.target.emit(&format!("{0:\n<1$}", "", amount), &state.settings());
-				self.emit_indentation(state, &state.settings())
+				self.emit(&state.settings().newline_style.as_str().repeat(amount), state.settings());
+				self.emit_indentation(state, state.settings())
 			},
 		}
 	}
 
 
+	/// Emit text with the line endings replaced by the configured style.
 	pub fn text(&mut self, text: &str, state: &State) {
+		if text.contains('\n').not() {
+			return self.emit(text, state.settings());
+		}
+		let text = text
+			.replace("\r\n", "\n")
+			.replace('\n', state.settings().newline_style.as_str());
+		self.emit(&text, state.settings());
+	}
+
+
+	/// Emit text unchanged, used for string literals.
+	pub fn literal(&mut self, text: &str, state: &State) {
 		if text.is_empty() {
 			return;
 		}
-		self.target.emit(text, &state.settings());
+		let text = std::mem::take(&mut self.pending) + text;
+		self.target.emit(&text, state.settings());
 	}
 
 
@@ -126,7 +233,7 @@ impl <'a, T: Target> Output<'a, T> {
 // 		for symbol in data.chars() {
 // 			match symbol {
 // 				'\t' => {
-// 					let tab_size = settings.indentation.max(1);
+// 					let tab_size = settings.tab_width.max(1);
 // 					self.column += 1 + tab_size.overflowing_sub(self.column).0 % tab_size
 // 				}
 // 				'\n' => {
@@ -150,3 +257,44 @@ impl <'a, T: Target> Output<'a, T> {
 // 		self.priority = Priority::Guaranteed;
 // 	}
 // }
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+
+	#[test]
+	fn columns_use_tab_width() {
+		let settings = Settings { tab_width: 8, ..Settings::default() };
+		assert_eq!(columns("\t  x", &settings), 10);
+		assert_eq!(columns("  \tx", &settings), 8);
+		assert_eq!(columns("\t", &Settings { tab_width: 0, ..Settings::default() }), 1);
+	}
+
+
+	#[test]
+	fn indentation_by_style() {
+		let tabs = Settings { tab_width: 4, ..Settings::default() };
+		assert_eq!(level_columns(&tabs), 4);
+		assert_eq!(indentation_text(10, &tabs), "\t\t  ");
+		let spaces = Settings { indent_style: IndentStyle::Spaces, indent_width: 2, tab_width: 8, ..Settings::default() };
+		assert_eq!(level_columns(&spaces), 2);
+		assert_eq!(indentation_text(5, &spaces), "     ");
+	}
+
+
+	#[test]
+	fn indentation_of_levels() {
+		let mut buffer = Vec::new();
+		let settings = Settings { indent_style: IndentStyle::Spaces, indent_width: 3, ..Settings::default() };
+		let mut state = State::new(settings);
+		state.indent();
+		state.indent();
+		let mut output = Output::new(&mut buffer);
+		output.whitespace(Whitespace::LineBreak, &state);
+		output.text("x", &state);
+		output.finish(&state);
+		assert_eq!(String::from_utf8(buffer).expect("output is UTF-8"), "\n      x\n");
+	}
+}
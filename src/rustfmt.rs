@@ -0,0 +1,88 @@
+/// Translate the options of a `rustfmt.toml` which have an equivalent setting.
+/// Returns the settings and the names of all options which could not be translated.
+pub fn import(data: &str) -> Result<(toml::Table, Vec<String>), toml::de::Error> {
+	let options = toml::from_str::<toml::Table>(data)?;
+	let mut settings = toml::Table::new();
+	let mut unmapped = Vec::new();
+	settings.insert("preset".into(), "rustfmt-like".into());
+
+	let hard_tabs = options.get("hard_tabs").and_then(toml::Value::as_bool).unwrap_or(false);
+	let tab_spaces = options.get("tab_spaces").and_then(toml::Value::as_integer).unwrap_or(4);
+	settings.insert("indent-style".into(), if hard_tabs { "tabs" } else { "spaces" }.into());
+	settings.insert("indent-width".into(), tab_spaces.into());
+	settings.insert("tab-width".into(), tab_spaces.into());
+	// `comment_width` only has an effect with `wrap_comments`, rustfmt defaults to 80.
+	if options.get("wrap_comments").and_then(toml::Value::as_bool).unwrap_or(false) {
+		let comment_width = options.get("comment_width").and_then(toml::Value::as_integer).unwrap_or(80);
+		settings.insert("comment-width".into(), comment_width.into());
+	}
+
+	for (option, value) in &options {
+		match (option.as_str(), value.as_str()) {
+			("hard_tabs" | "tab_spaces" | "wrap_comments" | "comment_width", _) => { }
+			("blank_lines_upper_bound", _) => _ = settings.insert("max-blank-lines-in-blocks".into(), value.clone()),
+			("format_code_in_doc_comments", _) => _ = settings.insert("format-doc-comment-code".into(), value.clone()),
+			("newline_style", Some("Unix")) => _ = settings.insert("newline-style".into(), "lf".into()),
+			("newline_style", Some("Windows")) => _ = settings.insert("newline-style".into(), "crlf".into()),
+			("newline_style", Some("Native")) => _ = settings.insert("newline-style".into(), "native".into()),
+			("newline_style", Some("Auto")) => _ = settings.insert("newline-style".into(), "auto".into()),
+			_ => unmapped.push(format!("{} = {}", option, value)),
+		}
+	}
+
+	Ok((settings, unmapped))
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+
+	#[test]
+	fn options_with_equivalents() {
+		let (settings, unmapped) = import("hard_tabs = true\ntab_spaces = 2\nnewline_style = \"Windows\"\nblank_lines_upper_bound = 3\nmax_width = 120\n")
+			.expect("valid TOML");
+		assert_eq!(settings.get("preset"), Some(&"rustfmt-like".into()));
+		assert_eq!(settings.get("indent-style"), Some(&"tabs".into()));
+		assert_eq!(settings.get("indent-width"), Some(&2.into()));
+		assert_eq!(settings.get("tab-width"), Some(&2.into()));
+		assert_eq!(settings.get("newline-style"), Some(&"crlf".into()));
+		assert_eq!(settings.get("max-blank-lines-in-blocks"), Some(&3.into()));
+		assert_eq!(unmapped, vec!["max_width = 120"]);
+		assert!(pretty_rusty::validation::validate(&toml::to_string(&settings).expect("settings serialize")).is_empty());
+	}
+
+
+	#[test]
+	fn defaults_of_rustfmt() {
+		let (settings, unmapped) = import("").expect("valid TOML");
+		assert_eq!(settings.get("indent-style"), Some(&"spaces".into()));
+		assert_eq!(settings.get("indent-width"), Some(&4.into()));
+		assert!(unmapped.is_empty());
+		assert!(import("hard_tabs = ").is_err());
+	}
+
+
+	#[test]
+	fn comment_width_requires_wrap_comments() {
+		let (settings, unmapped) = import("wrap_comments = true\ncomment_width = 100").expect("valid TOML");
+		assert_eq!(settings.get("comment-width"), Some(&100.into()));
+		assert!(unmapped.is_empty());
+
+		let (settings, _) = import("wrap_comments = true").expect("valid TOML");
+		assert_eq!(settings.get("comment-width"), Some(&80.into()));
+
+		let (settings, unmapped) = import("comment_width = 100").expect("valid TOML");
+		assert_eq!(settings.get("comment-width"), None);
+		assert!(unmapped.is_empty());
+	}
+
+
+	#[test]
+	fn format_code_in_doc_comments() {
+		let (settings, unmapped) = import("format_code_in_doc_comments = true").expect("valid TOML");
+		assert_eq!(settings.get("format-doc-comment-code"), Some(&true.into()));
+		assert!(unmapped.is_empty());
+	}
+}
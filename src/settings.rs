@@ -1,7 +1,8 @@
-use serde::{ Deserialize, Serialize };
+use std::{ collections::BTreeMap, ops::Not, path::Path };
+use serde::{ Deserialize, Deserializer, Serialize };
 
 
-trait Overwrite {
+pub(crate) trait Overwrite {
 	type Partial;
 
 
@@ -9,6 +10,42 @@ trait Overwrite {
 }
 
 
+pub(crate) trait Schema {
+	fn schema() -> serde_json::Value;
+}
+
+
+impl Schema for usize {
+	fn schema() -> serde_json::Value {
+		serde_json::json!({ "type": "integer", "minimum": 0 })
+	}
+}
+
+
+impl Schema for Option<usize> {
+	fn schema() -> serde_json::Value {
+		serde_json::json!({ "type": "integer", "minimum": 0 })
+	}
+}
+
+
+impl Overwrite for Option<usize> {
+	type Partial = usize;
+
+
+	fn overwrite(&mut self, other: usize) {
+		*self = Some(other);
+	}
+}
+
+
+impl Schema for bool {
+	fn schema() -> serde_json::Value {
+		serde_json::json!({ "type": "boolean" })
+	}
+}
+
+
 macro_rules! identity_overwrite {
     ($($t:ty),*$(,)?) => {
         $(
@@ -23,25 +60,93 @@ macro_rules! identity_overwrite {
 }
 
 
+macro_rules! schema_enum {
+    ($(#[$meta:meta])* pub enum $name:ident { $($(#[$variant_meta:meta])* $variant:ident,)* }) => {
+        $(#[$meta])*
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )*
+        }
+
+        impl Schema for $name {
+            /// A string, one of the variants serialized with serde.
+            fn schema() -> serde_json::Value {
+                let variants = [$(Self::$variant),*].map(|variant| serde_json::to_value(variant).unwrap_or_default());
+                serde_json::json!({ "type": "string", "enum": variants })
+            }
+        }
+    };
+}
+
+
 macro_rules! create_normal_and_partial {
     () => {};
-    (struct $name:ident | $partial_name:ident {$(pub $member:ident: $member_type:ty,)*} $($tail:tt)* ) => {
-        #[derive(Serialize, Debug)]
+    (struct $name:ident | $partial_name:ident {$($(#[doc = $doc:expr])* pub $member:ident: $member_type:ty,)*} $($tail:tt)* ) => {
+        #[derive(Serialize, Debug, Clone)]
         #[serde(rename_all = "kebab-case")]
         pub struct $name {
             $(
+                $(#[doc = $doc])*
                 pub $member: $member_type,
             )*
         }
 
+        impl $name {
+            /// JSON schema of every member with its documentation and the value from `defaults`.
+            fn schema_properties(defaults: &Self) -> serde_json::Map<String, serde_json::Value> {
+                let defaults = serde_json::to_value(defaults).unwrap_or_default();
+                let mut properties = serde_json::Map::new();
+                $(
+                    let key = stringify!($member).replace('_', "-");
+                    let mut schema = <$member_type as Schema>::schema();
+                    let description = [$($doc),*].map(str::trim).join("\n");
+                    schema["description"] = description.into();
+                    // Unset options have no default, `null` is not an integer.
+                    if defaults[&key].is_null().not() {
+                        schema["default"] = defaults[&key].clone();
+                    }
+                    properties.insert(key, schema);
+                )*
+                properties
+            }
+        }
+
         #[derive(Deserialize, Debug)]
         #[serde(rename_all = "kebab-case")]
-        struct $partial_name {
+        pub(crate) struct $partial_name {
             $(
                 pub $member: Option<<$member_type as Overwrite>::Partial>,
             )*
         }
 
+        impl $partial_name {
+            pub(crate) fn keys() -> Vec<String> {
+                vec![$(stringify!($member).replace('_', "-"),)*]
+            }
+
+            pub(crate) fn set_keys(&self) -> Vec<String> {
+                let mut keys = Vec::new();
+                $(
+                    if self.$member.is_some() {
+                        keys.push(stringify!($member).replace('_', "-"));
+                    }
+                )*
+                keys
+            }
+
+            /// Type check a single value, `None` if the key is unknown.
+            pub(crate) fn check(key: &str, value: toml::Value) -> Option<Result<(), toml::de::Error>> {
+                $(
+                    if key == stringify!($member).replace('_', "-") {
+                        return Some(value.try_into::<<$member_type as Overwrite>::Partial>().map(drop));
+                    }
+                )*
+                None
+            }
+        }
+
         impl Overwrite for $name {
             type Partial = $partial_name;
             fn overwrite(&mut self, other: $partial_name) {
@@ -57,6 +162,118 @@ macro_rules! create_normal_and_partial {
     };
 }
 
+
+schema_enum! {
+	#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+	#[serde(rename_all = "kebab-case")]
+	pub enum NewlineStyle {
+		Auto,
+		Lf,
+		Crlf,
+		Native,
+	}
+}
+
+
+impl NewlineStyle {
+	/// Replace `Auto` with the dominant line ending of `source` and `Native` with the line ending of the platform.
+	pub fn resolve(self, source: &str) -> Self {
+		match self {
+			Self::Auto => {
+				let crlf = source.matches("\r\n").count();
+				let lf = source.matches('\n').count() - crlf;
+				if crlf > lf { Self::Crlf } else { Self::Lf }
+			}
+			Self::Native if cfg!(windows) => Self::Crlf,
+			Self::Native => Self::Lf,
+			style => style,
+		}
+	}
+
+
+	/// Unresolved `Auto` is treated like `Lf`.
+	pub fn as_str(self) -> &'static str {
+		match self.resolve("") {
+			Self::Crlf => "\r\n",
+			_ => "\n",
+		}
+	}
+}
+
+
+schema_enum! {
+	#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+	#[serde(rename_all = "kebab-case")]
+	pub enum IndentStyle {
+		Tabs,
+		Spaces,
+	}
+}
+
+
+schema_enum! {
+	#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+	#[serde(rename_all = "kebab-case")]
+	pub enum Preset {
+		PrettyRusty,
+		RustfmtLike,
+		Compact,
+	}
+}
+
+
+schema_enum! {
+	/// How the token tree of a macro call is formatted.
+	#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+	#[serde(rename_all = "kebab-case")]
+	pub enum MacroShape {
+		/// Formatted like an argument list, array or block based on the delimiter if it parses.
+		#[default]
+		Auto,
+		/// Never changed.
+		Verbatim,
+		/// Formatted like a block of statements, regardless of the delimiter.
+		Block,
+		/// Formatted like an array, regardless of the delimiter.
+		List,
+	}
+}
+
+
+schema_enum! {
+	/// Style of doc comments.
+	#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+	#[serde(rename_all = "kebab-case")]
+	pub enum DocCommentStyle {
+		Preserve,
+		/// `///` and `//!`
+		Line,
+		/// `/** */` and `/*! */`
+		Block,
+	}
+}
+
+
+impl Schema for BTreeMap<String, MacroShape> {
+	fn schema() -> serde_json::Value {
+		serde_json::json!({
+			"type": "object",
+			"additionalProperties": MacroShape::schema(),
+		})
+	}
+}
+
+
+/// Tables are merged, names of later layers replace earlier ones.
+impl Overwrite for BTreeMap<String, MacroShape> {
+	type Partial = Self;
+
+
+	fn overwrite(&mut self, other: Self) {
+		self.extend(other);
+	}
+}
+
 // #[derive(Deserialize, Serialize, Debug)]
 // #[serde(rename_all = "kebab-case")]
 // pub enum UseLongBlock {
@@ -80,31 +297,258 @@ macro_rules! create_normal_and_partial {
 // }
 
 // identity_overwrite!(usize, bool, UseLongBlock, LongBlockStyle, AlignComma);
-identity_overwrite!(usize, bool);
+identity_overwrite!(usize, bool, NewlineStyle, IndentStyle, DocCommentStyle);
 
 
 create_normal_and_partial!(
 
 
     struct Settings | PartialSettings {
-        pub indentation: usize,
+        /// Indent with tabs or spaces.
+        pub indent_style: IndentStyle,
+        /// Spaces per indentation level with `indent-style = "spaces"`.
+        pub indent_width: usize,
+        /// Display width of a tab, used to compute columns.
+        pub tab_width: usize,
+        /// Indentation levels for continuation lines, like wrapped chains, operators and match guards.
+        pub continuation_indent: usize,
+        /// Indentation levels for wrapped method chains, `continuation-indent` if not set.
+        pub continuation_indent_chains: Option<usize>,
+        /// Indentation levels for wrapped binary operators, `continuation-indent` if not set.
+        pub continuation_indent_binary: Option<usize>,
+        /// Indentation levels for `where` predicates, `continuation-indent` if not set.
+        pub continuation_indent_where: Option<usize>,
+        /// End the output with a linebreak.
         pub final_newline: bool,
+        /// Line ending for every emitted linebreak.
+        pub newline_style: NewlineStyle,
 
+        /// Blank lines between top level items.
         pub blank_lines_around_items: usize,
+        /// Most consecutive blank lines kept inside blocks.
+        pub max_blank_lines_in_blocks: usize,
+        /// Blank lines kept between groups of `use` declarations.
+        pub blank_lines_between_use_groups: usize,
+        /// Most blank lines kept after the opening curly brace of a block.
+        pub blank_lines_after_block_open: usize,
 
+        /// Space inside single line parenthesis, `( a, b )`.
         pub pad_parenthesis : bool,
+        /// Space inside single line curly braces, `{ a, b }`.
         pub pad_curly_braces: bool,
+        /// Space inside single line square brackets, `[ a, b ]`.
         pub pad_square_brackets: bool,
+        /// Space inside single line angled brackets, `< A, B >`.
         pub pad_angled_brackets: bool,
+
+        /// Shape of the token tree of macro calls by macro name, `auto`, `verbatim`, `block` or `list`.
+        pub macros: BTreeMap<String, MacroShape>,
+        /// Format Rust code blocks in doc comments, hidden lines starting with `# ` are kept as they are.
+        pub format_doc_comment_code: bool,
+        /// Insert a space after `//`, `///` and `//!` if the comment starts right after them.
+        pub space_after_comment_marker: bool,
+        /// Convert doc comments to `line` or `block` comments, or `preserve` them.
+        pub doc_comment_style: DocCommentStyle,
+        /// Remove trailing whitespace at the end of comment lines.
+        pub trim_comment_trailing_whitespace: bool,
+        /// Wrap paragraphs of `//` and `///` comments with lines longer than this many columns, including indentation.
+        pub comment_width: Option<usize>,
+        /// Move the lines of code copied verbatim with the new indentation of their first line.
+        pub reindent_verbatim: bool,
+        /// Remove spaces and tabs at the end of every line, except inside string literals and hard breaks of doc comments.
+        pub trim_trailing_whitespace: bool,
     }
 );
 
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct Configuration {
+	preset: Option<Preset>,
+
+	/// Deprecated, replaced by `indent-style` and `indent-width`.
+	indentation: Option<usize>,
+
+	#[serde(flatten)]
+	settings: PartialSettings,
+
+	#[serde(default)]
+	overrides: Vec<PathOverride>,
+}
+
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct PathOverride {
+	#[serde(deserialize_with = "deserialize_globs")]
+	files: globset::GlobSet,
+
+	indentation: Option<usize>,
+
+	#[serde(flatten)]
+	settings: PartialSettings,
+}
+
+
+pub(crate) fn deserialize_globs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<globset::GlobSet, D::Error> {
+	let mut builder = globset::GlobSetBuilder::new();
+	for pattern in Vec::<String>::deserialize(deserializer)? {
+		let glob = globset::GlobBuilder::new(&pattern)
+			.literal_separator(true)
+			.build()
+			.map_err(serde::de::Error::custom)?;
+		builder.add(glob);
+	}
+	builder.build().map_err(serde::de::Error::custom)
+}
+
+
 impl Settings {
+	/// JSON schema for `pretty-rusty.toml`, including `[[overrides]]`.
+	pub fn schema() -> serde_json::Value {
+		let properties = Self::schema_properties(&Self::default());
+		let mut override_properties = properties.clone();
+		override_properties.insert("files".into(), serde_json::json!({
+			"type": "array",
+			"items": { "type": "string" },
+			"description": "Glob patterns relative to the configuration file.",
+		}));
+
+		let mut properties = properties;
+		let mut preset = Preset::schema();
+		preset["description"] = "Settings used before any other value is applied.".into();
+		preset["default"] = "pretty-rusty".into();
+		properties.insert("preset".into(), preset);
+		let indentation = serde_json::json!({
+			"type": "integer",
+			"minimum": 0,
+			"deprecated": true,
+			"description": "Deprecated, use `indent-style` and `indent-width`. Spaces per indentation level, 0 to indent with tabs.",
+		});
+		properties.insert("indentation".into(), indentation.clone());
+		override_properties.insert("indentation".into(), indentation);
+		properties.insert("overrides".into(), serde_json::json!({
+			"type": "array",
+			"description": "Settings for files matching the glob patterns in `files`.",
+			"items": {
+				"type": "object",
+				"properties": override_properties,
+				"required": ["files"],
+				"additionalProperties": false,
+			},
+		}));
+
+		serde_json::json!({
+			"$schema": "http://json-schema.org/draft-07/schema#",
+			"title": "pretty-rusty.toml",
+			"type": "object",
+			"properties": properties,
+			"additionalProperties": false,
+		})
+	}
+
+
+	pub fn from_preset(preset: Preset) -> Self {
+		match preset {
+			Preset::PrettyRusty => Self::default(),
+			Preset::RustfmtLike => Self {
+				indent_style: IndentStyle::Spaces,
+				indent_width: 4,
+				blank_lines_around_items: 1,
+				pad_curly_braces: false,
+				..Self::default()
+			},
+			Preset::Compact => Self {
+				blank_lines_around_items: 0,
+				pad_curly_braces: false,
+				..Self::default()
+			},
+		}
+	}
+
+
+	/// The preset selected by the configuration, if any.
+	pub fn preset(data: &str) -> Result<Option<Preset>, toml::de::Error> {
+		Ok(toml::from_str::<Configuration>(data)?.preset)
+	}
+
+
+	/// Apply the deprecated `indentation`, 0 for tabs, otherwise the number of spaces.
+	fn overwrite_indentation(&mut self, indentation: Option<usize>) -> Vec<String> {
+		match indentation {
+			None => Vec::new(),
+			Some(0) => {
+				self.indent_style = IndentStyle::Tabs;
+				vec!["indent-style".into()]
+			}
+			Some(width) => {
+				self.indent_style = IndentStyle::Spaces;
+				self.indent_width = width;
+				vec!["indent-style".into(), "indent-width".into()]
+			}
+		}
+	}
+
+
+	/// Settings from configuration layers in order, each with the path of the formatted file relative to it.
+	/// They start from the preset selected by the last layer with one.
+	/// Returns the keys which were set by each layer, entries of `macros` as `macros.NAME`.
+	pub fn from_layers(layers: &[(&str, Option<&Path>)]) -> Result<(Self, Vec<Vec<String>>), toml::de::Error> {
+		let configurations = layers.iter()
+			.map(|(data, path)| Ok((toml::from_str::<Configuration>(data)?, *path)))
+			.collect::<Result<Vec<_>, toml::de::Error>>()?;
+		let preset = configurations.iter().rev().find_map(|(configuration, _)| configuration.preset);
+		let mut settings = preset.map_or_else(Self::default, Self::from_preset);
+		let keys = configurations.into_iter()
+			.map(|(configuration, path)| settings.apply(configuration, path))
+			.collect();
+		Ok((settings, keys))
+	}
+
+
+	/// Fails for `preset`, which replaces every setting and is only applied by `from_layers`.
 	pub fn overwrite(&mut self, data: &str) -> Result<(), toml::de::Error> {
-		let partial = toml::from_str(data)?;
-		<Self as Overwrite>::overwrite(self, partial);
-		Ok(())
+		self.overwrite_for(data, None).map(drop)
+	}
+
+
+	/// Like `overwrite`, but also applies every `[[overrides]]` section whose `files` match `path`.
+	/// The path is relative to the folder of the configuration file.
+	/// Returns the keys which were set.
+	pub fn overwrite_for(&mut self, data: &str, path: Option<&Path>) -> Result<Vec<String>, toml::de::Error> {
+		let configuration = toml::from_str::<Configuration>(data)?;
+		if configuration.preset.is_some() {
+			return Err(serde::de::Error::custom("'preset' can only be applied with `Settings::from_layers`"));
+		}
+		Ok(self.apply(configuration, path))
+	}
+
+
+	fn apply(&mut self, configuration: Configuration, path: Option<&Path>) -> Vec<String> {
+		let mut keys = self.overwrite_indentation(configuration.indentation);
+		keys.extend(configuration.settings.set_keys());
+		keys.extend(configuration.settings.macro_keys());
+		<Self as Overwrite>::overwrite(self, configuration.settings);
+		let Some(path) = path else {
+			return keys;
+		};
+		for path_override in configuration.overrides {
+			if path_override.files.is_match(path) {
+				keys.extend(self.overwrite_indentation(path_override.indentation));
+				keys.extend(path_override.settings.set_keys());
+				keys.extend(path_override.settings.macro_keys());
+				<Self as Overwrite>::overwrite(self, path_override.settings);
+			}
+		}
+		keys
+	}
+}
+
+
+impl PartialSettings {
+	/// The `macros` tables of the layers are merged, so each entry has its own source.
+	fn macro_keys(&self) -> Vec<String> {
+		self.macros.iter().flatten().map(|(name, _)| format!("macros.{}", name)).collect()
 	}
 }
 
@@ -112,14 +556,122 @@ impl Settings {
 impl Default for Settings {
 	fn default() -> Self {
 		Self {
-			indentation: 0,
+			indent_style: IndentStyle::Tabs,
+			indent_width: 4,
+			tab_width: 4,
+			continuation_indent: 1,
+			continuation_indent_chains: None,
+			continuation_indent_binary: None,
+			continuation_indent_where: None,
 			final_newline: true,
+			newline_style: NewlineStyle::Auto,
 			blank_lines_around_items: 2,
+			max_blank_lines_in_blocks: 1,
+			blank_lines_between_use_groups: 1,
+			blank_lines_after_block_open: 0,
 
 			pad_parenthesis: false,
 			pad_curly_braces: true,
 			pad_square_brackets: false,
 			pad_angled_brackets: false,
+
+			macros: BTreeMap::new(),
+			format_doc_comment_code: false,
+			space_after_comment_marker: false,
+			doc_comment_style: DocCommentStyle::Preserve,
+			trim_comment_trailing_whitespace: false,
+			comment_width: None,
+			reindent_verbatim: false,
+			trim_trailing_whitespace: true,
 		}
 	}
 }
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+
+	#[test]
+	fn preset_of_last_layer_is_applied_first() {
+		let layers = [("preset = \"compact\"", None), ("preset = \"rustfmt-like\"\nindent-width = 2", None), ("", None)];
+		let (settings, keys) = Settings::from_layers(&layers).expect("layers are valid");
+		assert_eq!(settings.indent_style, IndentStyle::Spaces);
+		assert_eq!(settings.indent_width, 2);
+		assert_eq!(settings.blank_lines_around_items, 1);
+		assert_eq!(keys, vec![Vec::<String>::new(), vec!["indent-width".to_owned()], Vec::new()]);
+	}
+
+
+	#[test]
+	fn overwrite_rejects_preset() {
+		let mut settings = Settings::default();
+		assert!(settings.overwrite("preset = \"compact\"").is_err());
+		assert!(settings.overwrite("pad-parenthesis = true").is_ok());
+		assert!(settings.pad_parenthesis);
+	}
+
+
+	#[test]
+	fn schema_defaults_are_valid() {
+		let schema = Settings::schema();
+		let properties = &schema["properties"];
+		assert_eq!(properties["comment-width"].get("default"), None);
+		assert_eq!(properties["continuation-indent-chains"].get("default"), None);
+		assert_eq!(properties["indent-width"]["default"], 4);
+		assert_eq!(properties["newline-style"]["enum"], serde_json::json!(["auto", "lf", "crlf", "native"]));
+		assert_eq!(properties["preset"]["enum"], serde_json::json!(["pretty-rusty", "rustfmt-like", "compact"]));
+		assert_eq!(properties["macros"]["additionalProperties"]["enum"], serde_json::json!(["auto", "verbatim", "block", "list"]));
+	}
+
+
+	#[test]
+	fn overrides_apply_to_matching_paths() {
+		let data = "pad-parenthesis = true\n\n[[overrides]]\nfiles = [\"tests/**\"]\npad-parenthesis = false\nindentation = 2\n\n[[overrides]]\nfiles = [\"*.rs\"]\ntab-width = 8\n";
+		let mut settings = Settings::default();
+		let keys = settings.overwrite_for(data, Some(Path::new("tests/corpus/a.rs"))).expect("configuration is valid");
+		assert!(settings.pad_parenthesis.not());
+		assert_eq!((settings.indent_style, settings.indent_width), (IndentStyle::Spaces, 2));
+		assert_eq!(settings.tab_width, 4);
+		assert_eq!(keys, vec!["pad-parenthesis", "indent-style", "indent-width", "pad-parenthesis"]);
+
+		let mut settings = Settings::default();
+		settings.overwrite_for(data, Some(Path::new("main.rs"))).expect("configuration is valid");
+		assert!(settings.pad_parenthesis);
+		assert_eq!(settings.tab_width, 8);
+	}
+
+
+	#[test]
+	fn overrides_need_a_path() {
+		let mut settings = Settings::default();
+		settings.overwrite("[[overrides]]\nfiles = [\"**\"]\ntab-width = 8\n").expect("configuration is valid");
+		assert_eq!(settings.tab_width, 4);
+	}
+
+
+	#[test]
+	fn newline_style_resolution() {
+		assert_eq!(NewlineStyle::Auto.resolve("a\r\nb\r\nc\n"), NewlineStyle::Crlf);
+		assert_eq!(NewlineStyle::Auto.resolve("a\nb\r\nc\n"), NewlineStyle::Lf);
+		assert_eq!(NewlineStyle::Auto.resolve(""), NewlineStyle::Lf);
+		assert_eq!(NewlineStyle::Lf.resolve("a\r\n"), NewlineStyle::Lf);
+		assert_eq!(NewlineStyle::Native.resolve(""), if cfg!(windows) { NewlineStyle::Crlf } else { NewlineStyle::Lf });
+		assert_eq!(NewlineStyle::Crlf.as_str(), "\r\n");
+		assert_eq!(NewlineStyle::Auto.as_str(), "\n");
+	}
+
+
+	#[test]
+	fn deprecated_indentation_alias() {
+		let mut settings = Settings::default();
+		let keys = settings.overwrite_for("indentation = 2", None).expect("configuration is valid");
+		assert_eq!((settings.indent_style, settings.indent_width), (IndentStyle::Spaces, 2));
+		assert_eq!(keys, vec!["indent-style", "indent-width"]);
+		settings.overwrite("indentation = 0").expect("configuration is valid");
+		assert_eq!((settings.indent_style, settings.indent_width), (IndentStyle::Tabs, 2));
+		settings.overwrite("indentation = 3\nindent-width = 5").expect("configuration is valid");
+		assert_eq!(settings.indent_width, 5);
+	}
+}
@@ -1,18 +1,21 @@
-use std::ops::Not;
-use crate::settings::Settings;
+use crate::{ ast::SyntaxToken, settings::Settings, explain::Explanation, comments::Replacements };
 
 
 #[derive(Debug)]
 pub struct State {
 	settings: Settings,
 	indentation: usize,
-	chained: bool,
+	chained: Option<usize>,
+	explanations: Option<Vec<Explanation>>,
+	offset_shift: isize,
+	replacements: Replacements,
+	verbatim_shift: Option<isize>,
 }
 
 
 pub struct Save {
 	indetation: usize,
-	chained: bool,
+	chained: Option<usize>,
 }
 
 
@@ -21,19 +24,73 @@ impl State {
 		Self {
 			settings,
 			indentation: 0,
-			chained: false,
+			chained: None,
+			explanations: None,
+			offset_shift: 0,
+			replacements: Replacements::new(),
+			verbatim_shift: None,
 		}
 	}
 
 
+	pub fn record_explanations(&mut self) {
+		self.explanations = Some(Vec::new());
+	}
+
+
+	pub fn explain(&mut self, explanation: Explanation) {
+		if let Some(explanations) = &mut self.explanations {
+			explanations.push(explanation);
+		}
+	}
+
+
+	pub fn take_explanations(&mut self) -> Vec<Explanation> {
+		self.explanations.take().unwrap_or_default()
+	}
+
+
+	/// Difference between offsets in reparsed nodes and the original source.
+	pub fn offset_shift(&self) -> isize {
+		self.offset_shift
+	}
+
+
+	pub fn set_offset_shift(&mut self, offset_shift: isize) {
+		self.offset_shift = offset_shift;
+	}
+
+
+	pub fn set_replacements(&mut self, replacements: Replacements) {
+		self.replacements = replacements;
+	}
+
+
+	/// Lines replacing a comment, an empty list if the comment is part of the replacement of another one.
+	pub fn replacement(&self, token: &SyntaxToken) -> Option<&[String]> {
+		self.replacements.get(token).map(Vec::as_slice)
+	}
+
+
+	/// Columns added to the indentation of lines in the current verbatim region, if any.
+	pub fn verbatim_shift(&self) -> Option<isize> {
+		self.verbatim_shift
+	}
+
+
+	pub fn set_verbatim_shift(&mut self, shift: Option<isize>) {
+		self.verbatim_shift = shift;
+	}
+
+
 	pub fn indent(&mut self) {
 		self.indentation += 1;
-		self.chained = false;
+		self.chained = None;
 	}
 
 
 	pub fn enter_scope(&mut self) {
-		self.chained = false
+		self.chained = None
 	}
 
 
@@ -42,26 +99,25 @@ impl State {
 	}
 
 
-	pub fn start_chain(&mut self) {
-		if self.chained {
+	pub fn start_chain(&mut self, levels: usize) {
+		if self.chained.is_some() {
 			return;
 		}
-		self.chained = true;
-		self.indentation += 1;
+		self.chained = Some(levels);
+		self.indentation += levels;
 	}
 
 
 	pub fn in_chain(&self) -> bool {
-		self.chained
+		self.chained.is_some()
 	}
 
 
 	pub fn exit_chain(&mut self) {
-		if self.chained.not() {
+		let Some(levels) = self.chained.take() else {
 			return;
-		}
-		self.chained = false;
-		self.indentation -= 1;
+		};
+		self.indentation -= levels;
 	}
 
 
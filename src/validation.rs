@@ -0,0 +1,214 @@
+use std::{ collections::BTreeMap, ops::Range };
+use toml::Spanned;
+use serde::Deserialize;
+use crate::settings::{ PartialSettings, Preset, deserialize_globs };
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+	Warning,
+	Error,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub message: String,
+	pub span: Option<Range<usize>>,
+}
+
+
+impl Diagnostic {
+	fn error(message: impl Into<String>, span: Option<Range<usize>>) -> Self {
+		Self { severity: Severity::Error, message: message.into(), span }
+	}
+
+
+	/// Render like the compiler, with the line and column of the span and a snippet of the line.
+	pub fn render(&self, origin: &str, data: &str) -> String {
+		let severity = match self.severity {
+			Severity::Warning => "warning",
+			Severity::Error => "error",
+		};
+		let mut text = format!("{}: {}\n", severity, self.message);
+		let Some(span) = &self.span else {
+			text.push_str(&format!(" --> {}\n", origin));
+			return text;
+		};
+
+		let start = span.start.min(data.len());
+		let line_start = data[..start].rfind('\n').map_or(0, |index| index + 1);
+		let line_end = data[start..].find('\n').map_or(data.len(), |index| start + index);
+		let line = data[..start].matches('\n').count() + 1;
+		let column = data[line_start..start].chars().count() + 1;
+		let snippet = data[line_start..line_end].trim_end_matches('\r');
+		let marker = data[start..span.end.clamp(start, line_end)].chars().count().max(1);
+		let gutter = line.to_string().len();
+
+		text.push_str(&format!("{0: <1$}--> {2}:{3}:{4}\n", "", gutter, origin, line, column));
+		text.push_str(&format!("{0: <1$} |\n", "", gutter));
+		text.push_str(&format!("{} | {}\n", line, snippet));
+		text.push_str(&format!("{0: <1$} | {0: <2$}{0:^<3$}\n", "", gutter, column - 1, marker));
+		text
+	}
+}
+
+
+type SpannedTable = BTreeMap<Spanned<String>, Spanned<toml::Value>>;
+
+
+#[derive(Deserialize)]
+struct Overrides {
+	#[serde(default)]
+	overrides: Vec<SpannedTable>,
+}
+
+
+/// Check a configuration for syntax errors, unknown keys and values with the wrong type.
+pub fn validate(data: &str) -> Vec<Diagnostic> {
+	let table = match toml::from_str::<SpannedTable>(data) {
+		Ok(table) => table,
+		Err(err) => return vec![Diagnostic::error(err.message(), err.span())],
+	};
+
+	let mut diagnostics = Vec::new();
+	check_table(&table, &["overrides", "preset", "indentation"], &mut diagnostics);
+	check_deprecated(&table, &mut diagnostics);
+	if let Some((_, preset)) = table.iter().find(|(key, _)| key.get_ref() == "preset") {
+		if let Err(err) = preset.get_ref().clone().try_into::<Preset>() {
+			diagnostics.push(Diagnostic::error(format!("invalid value for 'preset': {}", err.message()), Some(preset.span())));
+		}
+	}
+
+	match toml::from_str::<Overrides>(data) {
+		Ok(overrides) => for table in &overrides.overrides {
+			check_table(table, &["files", "indentation"], &mut diagnostics);
+			check_deprecated(table, &mut diagnostics);
+			match table.iter().find(|(key, _)| key.get_ref() == "files") {
+				Some((_, files)) => if let Err(err) = deserialize_globs(files.get_ref().clone()) {
+					diagnostics.push(Diagnostic::error(err.message(), Some(files.span())));
+				},
+				None => diagnostics.push(Diagnostic::error("override without 'files'", None)),
+			}
+		},
+		Err(err) => diagnostics.push(Diagnostic::error(err.message(), err.span())),
+	}
+
+	diagnostics.sort_by_key(|diagnostic| diagnostic.span.as_ref().map(|span| span.start));
+	diagnostics
+}
+
+
+fn check_table(table: &SpannedTable, extra_keys: &[&str], diagnostics: &mut Vec<Diagnostic>) {
+	for (key, value) in table {
+		if extra_keys.contains(&key.get_ref().as_str()) {
+			continue;
+		}
+		match PartialSettings::check(key.get_ref(), value.get_ref().clone()) {
+			Some(Ok(())) => { }
+			Some(Err(err)) => diagnostics.push(Diagnostic::error(
+				format!("invalid value for '{}': {}", key.get_ref(), err.message()),
+				Some(value.span()),
+			)),
+			None => diagnostics.push(Diagnostic {
+				severity: Severity::Warning,
+				message: unknown_key_message(key.get_ref(), extra_keys),
+				span: Some(key.span()),
+			}),
+		}
+	}
+}
+
+
+fn check_deprecated(table: &SpannedTable, diagnostics: &mut Vec<Diagnostic>) {
+	let Some((key, value)) = table.iter().find(|(key, _)| key.get_ref() == "indentation") else {
+		return;
+	};
+	diagnostics.push(Diagnostic {
+		severity: Severity::Warning,
+		message: "'indentation' is deprecated, use 'indent-style' and 'indent-width'".into(),
+		span: Some(key.span()),
+	});
+	if let Err(err) = value.get_ref().clone().try_into::<usize>() {
+		diagnostics.push(Diagnostic::error(format!("invalid value for 'indentation': {}", err.message()), Some(value.span())));
+	}
+}
+
+
+fn unknown_key_message(key: &str, extra_keys: &[&str]) -> String {
+	let suggestion = PartialSettings::keys()
+		.into_iter()
+		.chain(extra_keys.iter().map(|key| key.to_string()))
+		.map(|known| (strsim::levenshtein(key, &known), known))
+		.filter(|(distance, _)| *distance <= (key.len() / 3).max(2))
+		.min();
+	match suggestion {
+		Some((_, known)) => format!("unknown key '{}', did you mean '{}'?", key, known),
+		None => format!("unknown key '{}'", key),
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+
+	#[test]
+	fn unknown_keys_have_suggestions() {
+		let diagnostics = validate("pad-parentesis = true\nunrelated-option = 1\n");
+		let messages = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<_>>();
+		assert_eq!(messages, vec!["unknown key 'pad-parentesis', did you mean 'pad-parenthesis'?", "unknown key 'unrelated-option'"]);
+		assert!(diagnostics.iter().all(|diagnostic| diagnostic.severity == Severity::Warning));
+		assert_eq!(diagnostics[0].span, Some(0..14));
+	}
+
+
+	#[test]
+	fn invalid_values_are_errors() {
+		let data = "tab-width = \"wide\"\npreset = \"fancy\"\n";
+		let diagnostics = validate(data);
+		assert_eq!(diagnostics.len(), 2);
+		assert!(diagnostics.iter().all(|diagnostic| diagnostic.severity == Severity::Error));
+		assert_eq!(diagnostics[0].span.clone().map(|span| &data[span]), Some("\"wide\""));
+		assert!(diagnostics[1].message.starts_with("invalid value for 'preset'"));
+	}
+
+
+	#[test]
+	fn overrides_are_checked() {
+		let diagnostics = validate("[[overrides]]\ntab-widht = 2\n\n[[overrides]]\nfiles = [\"a/**\"]\n");
+		let messages = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<_>>();
+		assert_eq!(messages, vec!["override without 'files'", "unknown key 'tab-widht', did you mean 'tab-width'?"]);
+	}
+
+
+	#[test]
+	fn deprecated_indentation() {
+		let diagnostics = validate("indentation = 4\n");
+		assert_eq!(diagnostics.len(), 1);
+		assert_eq!(diagnostics[0].severity, Severity::Warning);
+		assert!(validate("pad-parenthesis = true\n").is_empty());
+	}
+
+
+	#[test]
+	fn syntax_errors() {
+		let diagnostics = validate("tab-width = \n");
+		assert_eq!(diagnostics.len(), 1);
+		assert_eq!(diagnostics[0].severity, Severity::Error);
+	}
+
+
+	#[test]
+	fn render_points_at_the_span() {
+		let data = "a = 1\ntab-width = \"wide\"\n";
+		let diagnostic = Diagnostic::error("invalid", Some(18..24));
+		assert_eq!(
+			diagnostic.render("pretty-rusty.toml", data),
+			"error: invalid\n --> pretty-rusty.toml:2:13\n  |\n2 | tab-width = \"wide\"\n  |             ^^^^^^\n",
+		);
+		assert_eq!(Diagnostic::error("invalid", None).render("file", data), "error: invalid\n --> file\n");
+	}
+}
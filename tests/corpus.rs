@@ -0,0 +1,38 @@
+//! Formats every `tests/corpus/NAME.rs` and compares it with `NAME.expected.rs`.
+//! The settings are the defaults, overwritten by `NAME.toml` if it exists.
+
+use std::{ ops::Not, path::Path };
+use pretty_rusty::{ ast, Settings };
+
+
+fn format(source: &str, settings: &Settings) -> String {
+	let mut output = Vec::new();
+	let root = ast::SourceFile::parse(source).syntax_node();
+	pretty_rusty::format_node(root, settings.clone(), &mut output);
+	String::from_utf8(output).expect("output is valid UTF-8")
+}
+
+
+#[test]
+fn corpus() {
+	let folder = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+	let mut inputs = std::fs::read_dir(&folder)
+		.expect("corpus folder exists")
+		.map(|entry| entry.expect("corpus entry is readable").path())
+		.filter(|path| path.extension().is_some_and(|extension| extension == "rs"))
+		.filter(|path| path.to_string_lossy().ends_with(".expected.rs").not())
+		.collect::<Vec<_>>();
+	inputs.sort();
+	assert!(inputs.is_empty().not(), "corpus is empty");
+
+	for input in inputs {
+		let expected = input.with_extension("expected.rs");
+		let source = std::fs::read_to_string(&input).expect("input is readable");
+		let expected = std::fs::read_to_string(&expected).expect("expected output is readable");
+		let data = std::fs::read_to_string(input.with_extension("toml")).unwrap_or_default();
+		let (settings, _) = Settings::from_layers(&[(&data, None)]).expect("settings are valid");
+		let formatted = format(&source, &settings);
+		assert_eq!(formatted, expected, "{}", input.display());
+		assert_eq!(format(&formatted, &settings), formatted, "{} is not idempotent", input.display());
+	}
+}
@@ -0,0 +1,6 @@
+fn main() {
+	let a = 1;
+
+	let b = 2;
+	let c = 3;
+}
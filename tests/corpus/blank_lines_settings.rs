@@ -0,0 +1,10 @@
+fn f() {
+
+
+	let a = 1;
+
+
+
+	let b = 2;
+}
+fn g() {}
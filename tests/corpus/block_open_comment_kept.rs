@@ -0,0 +1,19 @@
+fn f() {
+
+
+	// c
+	let a = 1;
+}
+
+impl S {
+
+	// d
+	fn g() {}
+}
+
+mod m {
+
+
+	// e
+	fn h() {}
+}
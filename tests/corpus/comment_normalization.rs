@@ -0,0 +1,12 @@
+//!Crate.   
+
+//no space
+/**
+ * Block doc.   
+ *
+ * Second.
+ */
+fn f() {
+	let x = 1; //trailing
+	//// separator
+}
@@ -0,0 +1,20 @@
+fn main() {
+	// plain comment which is long
+	// enough to be wrapped by the
+	// reflow algorithm
+	// let x = 1;
+	let y = 2; // trailing comment which is long but must not be touched
+}
+
+
+/// A doc comment line that definitely
+/// goes past the configured width.
+///
+/// - a list item that is long enough to
+///   need wrapping here
+/// - short
+///
+/// ```
+/// let some_very_long_variable_name = another_function(argument);
+/// ```
+fn f() { }
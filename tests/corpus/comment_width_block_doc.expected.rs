@@ -0,0 +1,3 @@
+/// Returns the value as a usize, panicking if it does not
+/// fit, which can only happen on sixteen bit targets.
+fn f() { }
@@ -0,0 +1,6 @@
+fn main() {
+	let a = 1; // x
+	// a long paragraph right after a trailing comment which
+	// has to be wrapped at sixty columns and continues here
+	let b = 2;
+}
@@ -0,0 +1,7 @@
+fn main() {
+	let a = 1;
+	let b = 2; // comment
+	/* block
+	   comment */
+	foo(a, b);
+}
@@ -0,0 +1,5 @@
+fn f() {
+	let x = a
+		.b()
+		.c();
+}
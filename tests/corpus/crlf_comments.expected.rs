@@ -0,0 +1,13 @@
+fn main() {
+	// c
+	let a = 1; // d
+}
+
+
+fn g() {
+	// pretty-rusty: off
+	let  a = 1; // d
+	// pretty-rusty: on
+	json!({ // x
+	});
+}
@@ -0,0 +1,6 @@
+/**
+ * First.
+ *
+ * Second.
+ */
+fn f() { }
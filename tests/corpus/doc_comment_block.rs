@@ -0,0 +1,4 @@
+/// First.
+///
+/// Second.
+fn f() {}
@@ -0,0 +1,10 @@
+/// Adds.
+///
+/// ```rust
+/// let x=add(1,2);
+/// ```
+///
+/// ```text
+/// not   rust
+/// ```
+fn add(a: usize, b: usize) -> usize { a + b }
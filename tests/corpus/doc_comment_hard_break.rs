@@ -0,0 +1,5 @@
+/// First line with a hard break  
+/// second line   
+//! inner  
+// plain comment  
+fn f() {}   
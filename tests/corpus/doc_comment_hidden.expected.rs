@@ -0,0 +1,19 @@
+/// Reads.
+///
+/// ```
+/// # use std::io;
+/// # fn main() -> io::Result<()> {
+/// let x = io::stdin();
+/// #
+/// # Ok(())
+/// # }
+/// ```
+fn read() { }
+
+
+/// ```
+/// # fn main() {
+/// let y = [1, 2];
+/// # }
+/// ```
+fn other() { }
@@ -0,0 +1,10 @@
+struct S { /** field a */ a: u32 }
+
+
+fn f(/** x */ x: u32) { }
+
+
+struct T {
+	/// field b
+	b: u32,
+}
@@ -0,0 +1,5 @@
+fn main() {
+	let x = ;   
+	let y = (1,   
+		2);
+}
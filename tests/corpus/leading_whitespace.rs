@@ -0,0 +1,7 @@
+
+
+use a;use b;
+fn f() {
+
+	let x = 1;let y = 2;
+}
@@ -0,0 +1,7 @@
+macro_rules! m {
+	($k:expr => $v:expr) => { ($k, $v) };
+	($a:expr, $($b:ident),*) => { $a };
+	($a:expr, $($b:ident),* $(,)?) => { $a };
+	($($t:tt)::+; $x:ident ..= $y:ident) => (foo($x));
+	(@x $e:tt) => [$e];
+}
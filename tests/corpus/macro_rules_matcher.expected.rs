@@ -0,0 +1,5 @@
+macro_rules! m {
+	($(#[$attr:meta])* fn $name:ident() -> $ret:ty) => { 1 };
+	(&$x:expr, -$y:expr, !$z:expr) => { 2 };
+	($a:expr - $b:expr, ($c:expr) ($d:tt)) => { 3 };
+}
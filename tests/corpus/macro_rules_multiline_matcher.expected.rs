@@ -0,0 +1,11 @@
+macro_rules! m {
+	(
+		$x:expr,
+		$y:expr,
+			$z:expr
+	) => { 5 };
+	(
+		$a:expr, // first
+		$b:expr
+	) => { 6 };
+}
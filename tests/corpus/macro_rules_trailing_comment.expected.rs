@@ -0,0 +1,5 @@
+macro_rules! m {
+	($x:expr) => { 4 }; // after the arm
+	// before the arm
+	($x:expr, $y:expr) => { 5 };
+}
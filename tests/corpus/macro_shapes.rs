@@ -0,0 +1,9 @@
+fn main() {
+	let a = json!({ "a" :1 });
+	let b = my_vec!(1,2,   3);
+	select!(
+		let x=1;
+		foo(x)
+	);
+	let d = foo!(a,b);
+}
@@ -0,0 +1,4 @@
+fn f() {
+	let s = "a
+b";
+}
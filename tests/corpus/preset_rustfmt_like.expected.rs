@@ -0,0 +1,7 @@
+fn f() {
+    let x = 1;
+}
+
+struct S {a: u8}
+
+fn g() {}
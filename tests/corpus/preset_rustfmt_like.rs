@@ -0,0 +1,5 @@
+fn f() {
+	let x = 1;
+}
+struct S { a: u8 }
+fn g() {}
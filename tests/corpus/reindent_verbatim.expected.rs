@@ -0,0 +1,11 @@
+mod m {
+	fn main() {
+		#[rustfmt::skip]
+		let m = [
+			1,0,
+			0,1,
+		];
+		let s = "a
+                    b";
+	}
+}
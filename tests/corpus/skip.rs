@@ -0,0 +1,10 @@
+fn main() {
+	#[rustfmt::skip]
+	let m = [   
+		1,0,   
+		0,1,	
+	];
+	// pretty-rusty: off
+	let  n  =  1;   
+	// pretty-rusty: on
+}
@@ -0,0 +1,5 @@
+#![rustfmt::skip]
+
+fn  main ( )  {
+    let x=1 ;
+}
@@ -0,0 +1,7 @@
+#![rustfmt::skip]
+
+fn  main ( )  {
+    let x=1 ;
+}
+
+
@@ -0,0 +1,10 @@
+fn main() {
+	let s = "line   
+  next   ";
+	let r = r#"raw   
+	raw   "#;
+	let b = b"bytes   
+";
+	let c = c"c   
+";
+}
@@ -0,0 +1,7 @@
+fn main() {
+	json!({
+		"a" :   1,
+		"b": "keep   
+   this"
+	});
+}